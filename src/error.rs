@@ -8,10 +8,15 @@ pub enum Error {
     GDeflate(#[from] crate::gdf::Error),
     #[error("DDS error: {0}")]
     Dds(#[from] ddsfile::Error),
+    #[error("Swizzle error: {0}")]
+    Swizzle(#[from] tegra_swizzle::SwizzleError),
 
     #[cfg(feature = "image")]
     #[error("Create image from DDS error: {0}")]
     CreateImageDds(#[from] image_dds::error::CreateImageError),
+    #[cfg(feature = "image")]
+    #[error("Create DDS from image error: {0}")]
+    CreateDdsImage(#[from] image_dds::CreateDdsError),
 
     #[error("Not a Tex file.")]
     NotTexFile,
@@ -23,4 +28,14 @@ pub enum Error {
     Unimplemented(String),
     #[error("Unsupported Tex format: 0x{0:X}")]
     UnsupportedTexFormat(u32),
+    #[error("Not supported by Tex version {0}: {1}")]
+    UnsupportedByVersion(u32, String),
+    #[error("Tex format {0:?} has no DXGI equivalent.")]
+    NoDxgiFormat(crate::format::TexFormat),
+    #[error("Unsupported DXGI format: {0}")]
+    UnsupportedDxgiFormat(u32),
+    #[error("Unsupported DDS format.")]
+    UnsupportedDdsFormat,
+    #[error("Invalid DDS data: {0}")]
+    InvalidDdsData(String),
 }
@@ -1,5 +1,9 @@
+use ddsfile::DxgiFormat;
 use strum::FromRepr;
 
+use crate::error::{Error, Result};
+
+#[deprecated(note = "unused, use TexFormat::is_astc, is_typeless and is_srgb")]
 #[derive(Debug, Clone)]
 pub enum TexFormatFamily {
     Astc {
@@ -10,8 +14,10 @@ pub enum TexFormatFamily {
 }
 
 #[repr(u32)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromRepr)]
-#[cfg_attr(debug_assertions, derive(strum::IntoStaticStr, strum::EnumIter))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromRepr, strum::EnumString)]
+#[cfg_attr(debug_assertions, derive(strum::IntoStaticStr))]
+#[cfg_attr(any(test, debug_assertions), derive(strum::EnumIter))]
+#[strum(ascii_case_insensitive)]
 pub enum TexFormat {
     A8Unorm = 0x41,
     Astc10x10Typeless = 0x422,
@@ -159,13 +165,51 @@ pub enum TexFormat {
 }
 
 impl TexFormat {
+    /// Get the matching image_dds format, used for encoding and decoding images.
+    #[cfg(feature = "image")]
+    pub(crate) fn to_image_format(self) -> Option<image_dds::ImageFormat> {
+        use image_dds::ImageFormat;
+
+        let format = match self {
+            TexFormat::Bc1Typeless | TexFormat::Bc1Unorm => ImageFormat::BC1RgbaUnorm,
+            TexFormat::Bc1UnormSrgb => ImageFormat::BC1RgbaUnormSrgb,
+            TexFormat::Bc2Typeless | TexFormat::Bc2Unorm => ImageFormat::BC2RgbaUnorm,
+            TexFormat::Bc2UnormSrgb => ImageFormat::BC2RgbaUnormSrgb,
+            TexFormat::Bc3Typeless | TexFormat::Bc3Unorm => ImageFormat::BC3RgbaUnorm,
+            TexFormat::Bc3UnormSrgb => ImageFormat::BC3RgbaUnormSrgb,
+            TexFormat::Bc4Typeless | TexFormat::Bc4Unorm => ImageFormat::BC4RUnorm,
+            TexFormat::Bc4Snorm => ImageFormat::BC4RSnorm,
+            TexFormat::Bc5Typeless | TexFormat::Bc5Unorm => ImageFormat::BC5RgUnorm,
+            TexFormat::Bc5Snorm => ImageFormat::BC5RgSnorm,
+            TexFormat::Bc6hTypeless | TexFormat::Bc6hUF16 => ImageFormat::BC6hRgbUfloat,
+            TexFormat::Bc6hSF16 => ImageFormat::BC6hRgbSfloat,
+            TexFormat::Bc7Typeless | TexFormat::Bc7Unorm => ImageFormat::BC7RgbaUnorm,
+            TexFormat::Bc7UnormSrgb => ImageFormat::BC7RgbaUnormSrgb,
+            TexFormat::R8Typeless | TexFormat::R8Unorm => ImageFormat::R8Unorm,
+            TexFormat::R8Snorm => ImageFormat::R8Snorm,
+            TexFormat::R8G8Typeless | TexFormat::R8G8Unorm => ImageFormat::Rg8Unorm,
+            TexFormat::R8G8Snorm => ImageFormat::Rg8Snorm,
+            TexFormat::R8G8B8A8Typeless | TexFormat::R8G8B8A8Unorm => ImageFormat::Rgba8Unorm,
+            TexFormat::R8G8B8A8UnormSrgb => ImageFormat::Rgba8UnormSrgb,
+            TexFormat::R8G8B8A8Snorm => ImageFormat::Rgba8Snorm,
+            TexFormat::B8G8R8A8Typeless | TexFormat::B8G8R8A8Unorm => ImageFormat::Bgra8Unorm,
+            TexFormat::B8G8R8A8UnormSrgb => ImageFormat::Bgra8UnormSrgb,
+            TexFormat::R16G16B16A16Float => ImageFormat::Rgba16Float,
+            TexFormat::R32G32B32A32Float => ImageFormat::Rgba32Float,
+            _ => return None,
+        };
+        Some(format)
+    }
+
     pub fn is_astc(&self) -> bool {
-        (*self) as u32 & 0x400 != 0
+        let val = (*self) as u32;
+        (0x401..=0x42A).contains(&val)
     }
 
     pub fn is_bc(&self) -> bool {
         let val = (*self) as u32;
-        (0x46..=0x63).contains(&val)
+        // B5G6R5 to B8G8R8X8 sit between BC5 and BC6H
+        (0x46..=0x54).contains(&val) || (0x5E..=0x63).contains(&val)
     }
 
     pub fn is_rgb(&self) -> bool {
@@ -246,6 +290,534 @@ impl TexFormat {
     }
 }
 
+/// ASTC block dimensions, in the order of the format values.
+const ASTC_BLOCK_DIMENSIONS: [(u32, u32); 14] = [
+    (4, 4),
+    (5, 4),
+    (5, 5),
+    (6, 5),
+    (6, 6),
+    (8, 5),
+    (8, 6),
+    (8, 8),
+    (10, 5),
+    (10, 6),
+    (10, 8),
+    (10, 10),
+    (12, 10),
+    (12, 12),
+];
+
+/// Format metadata.
+impl TexFormat {
+    /// Index of an ASTC format in [`ASTC_BLOCK_DIMENSIONS`] and its variant:
+    /// 0 typeless, 1 unorm, 2 unorm sRGB.
+    fn astc_index(&self) -> Option<(usize, u32)> {
+        if !self.is_astc() {
+            return None;
+        }
+        let val = (*self) as u32 - 0x401;
+        Some(((val / 3) as usize, val % 3))
+    }
+
+    /// Width and height of a block in pixels.
+    ///
+    /// Uncompressed formats have 1x1 blocks, except the packed 4:2:2 formats
+    /// and `R1Unorm`, which stores 8 pixels per byte.
+    pub fn block_dimensions(&self) -> (u32, u32) {
+        if let Some((idx, _)) = self.astc_index() {
+            return ASTC_BLOCK_DIMENSIONS[idx];
+        }
+        match self {
+            _ if self.is_bc() => (4, 4),
+            TexFormat::R8G8B8G8Unorm | TexFormat::G8R8G8B8Unorm => (2, 1),
+            TexFormat::R1Unorm => (8, 1),
+            _ => (1, 1),
+        }
+    }
+
+    /// Size of a block in bytes, which is the pixel size for uncompressed formats.
+    ///
+    /// `None` for the placeholder formats `ViaExtension` and `ForceUint`.
+    pub fn bytes_per_block(&self) -> Option<u32> {
+        let size = match self {
+            TexFormat::ViaExtension | TexFormat::ForceUint => return None,
+            _ if self.is_astc() => 16,
+            TexFormat::Bc1Typeless
+            | TexFormat::Bc1Unorm
+            | TexFormat::Bc1UnormSrgb
+            | TexFormat::Bc4Typeless
+            | TexFormat::Bc4Unorm
+            | TexFormat::Bc4Snorm => 8,
+            _ if self.is_bc() => 16,
+            TexFormat::R32G32B32A32Typeless
+            | TexFormat::R32G32B32A32Float
+            | TexFormat::R32G32B32A32Uint
+            | TexFormat::R32G32B32A32Sint => 16,
+            TexFormat::R32G32B32Typeless
+            | TexFormat::R32G32B32Float
+            | TexFormat::R32G32B32Uint
+            | TexFormat::R32G32B32Sint => 12,
+            TexFormat::R16G16B16A16Typeless
+            | TexFormat::R16G16B16A16Float
+            | TexFormat::R16G16B16A16Unorm
+            | TexFormat::R16G16B16A16Uint
+            | TexFormat::R16G16B16A16Snorm
+            | TexFormat::R16G16B16A16Sint
+            | TexFormat::R32G32Typeless
+            | TexFormat::R32G32Float
+            | TexFormat::R32G32Uint
+            | TexFormat::R32G32Sint
+            | TexFormat::R32G8X24Typeless
+            | TexFormat::D32FloatS8X24Uint
+            | TexFormat::R32FloatX8X24Typeless
+            | TexFormat::X32TypelessG8X24Uint => 8,
+            TexFormat::R8G8Typeless
+            | TexFormat::R8G8Unorm
+            | TexFormat::R8G8Uint
+            | TexFormat::R8G8Snorm
+            | TexFormat::R8G8Sint
+            | TexFormat::R16Typeless
+            | TexFormat::R16Float
+            | TexFormat::D16Unorm
+            | TexFormat::R16Unorm
+            | TexFormat::R16Uint
+            | TexFormat::R16Snorm
+            | TexFormat::R16Sint
+            | TexFormat::B5G6R5Unorm
+            | TexFormat::B5G5R5A1Unorm => 2,
+            TexFormat::R8Typeless
+            | TexFormat::R8Unorm
+            | TexFormat::R8Uint
+            | TexFormat::R8Snorm
+            | TexFormat::R8Sint
+            | TexFormat::A8Unorm
+            | TexFormat::R1Unorm => 1,
+            // 32-bit formats, including the packed 4:2:2 formats with 2 pixels per block
+            _ => 4,
+        };
+        Some(size)
+    }
+
+    /// Number of color channels, ignoring unused `X` channels.
+    pub fn channel_count(&self) -> u8 {
+        match self {
+            TexFormat::ViaExtension | TexFormat::ForceUint => 0,
+            _ if self.is_astc() => 4,
+            TexFormat::R8Typeless
+            | TexFormat::R8Unorm
+            | TexFormat::R8Uint
+            | TexFormat::R8Snorm
+            | TexFormat::R8Sint
+            | TexFormat::A8Unorm
+            | TexFormat::R1Unorm
+            | TexFormat::R16Typeless
+            | TexFormat::R16Float
+            | TexFormat::D16Unorm
+            | TexFormat::R16Unorm
+            | TexFormat::R16Uint
+            | TexFormat::R16Snorm
+            | TexFormat::R16Sint
+            | TexFormat::R32Typeless
+            | TexFormat::D32Float
+            | TexFormat::R32Float
+            | TexFormat::R32Uint
+            | TexFormat::R32Sint
+            | TexFormat::R24UnormX8Typeless
+            | TexFormat::X24TypelessG8Uint
+            | TexFormat::R32FloatX8X24Typeless
+            | TexFormat::X32TypelessG8X24Uint
+            | TexFormat::Bc4Typeless
+            | TexFormat::Bc4Unorm
+            | TexFormat::Bc4Snorm => 1,
+            TexFormat::R8G8Typeless
+            | TexFormat::R8G8Unorm
+            | TexFormat::R8G8Uint
+            | TexFormat::R8G8Snorm
+            | TexFormat::R8G8Sint
+            | TexFormat::R16G16Typeless
+            | TexFormat::R16G16Float
+            | TexFormat::R16G16Unorm
+            | TexFormat::R16G16Uint
+            | TexFormat::R16G16Snorm
+            | TexFormat::R16G16Sint
+            | TexFormat::R32G32Typeless
+            | TexFormat::R32G32Float
+            | TexFormat::R32G32Uint
+            | TexFormat::R32G32Sint
+            | TexFormat::R32G8X24Typeless
+            | TexFormat::D32FloatS8X24Uint
+            | TexFormat::R24G8Typeless
+            | TexFormat::D24UnormS8Uint
+            | TexFormat::Bc5Typeless
+            | TexFormat::Bc5Unorm
+            | TexFormat::Bc5Snorm => 2,
+            TexFormat::R32G32B32Typeless
+            | TexFormat::R32G32B32Float
+            | TexFormat::R32G32B32Uint
+            | TexFormat::R32G32B32Sint
+            | TexFormat::R11G11B10Float
+            | TexFormat::R9G9B9E5Sharedexp
+            | TexFormat::R8G8B8G8Unorm
+            | TexFormat::G8R8G8B8Unorm
+            | TexFormat::B5G6R5Unorm
+            | TexFormat::B8G8R8X8Typeless
+            | TexFormat::B8G8R8X8Unorm
+            | TexFormat::B8G8R8X8UnormSrgb
+            | TexFormat::Bc6hTypeless
+            | TexFormat::Bc6hUF16
+            | TexFormat::Bc6hSF16 => 3,
+            _ => 4,
+        }
+    }
+
+    /// Whether color values are stored in sRGB space.
+    pub fn is_srgb(&self) -> bool {
+        if let Some((_, variant)) = self.astc_index() {
+            return variant == 2;
+        }
+        matches!(
+            self,
+            TexFormat::R8G8B8A8UnormSrgb
+                | TexFormat::B8G8R8A8UnormSrgb
+                | TexFormat::B8G8R8X8UnormSrgb
+                | TexFormat::Bc1UnormSrgb
+                | TexFormat::Bc2UnormSrgb
+                | TexFormat::Bc3UnormSrgb
+                | TexFormat::Bc7UnormSrgb
+        )
+    }
+
+    /// Whether the color channels have no type, and are interpreted by views.
+    ///
+    /// Depth view formats with only a typeless padding channel,
+    /// like `R24UnormX8Typeless`, are not typeless.
+    pub fn is_typeless(&self) -> bool {
+        if let Some((_, variant)) = self.astc_index() {
+            return variant == 0;
+        }
+        matches!(
+            self,
+            TexFormat::R32G32B32A32Typeless
+                | TexFormat::R32G32B32Typeless
+                | TexFormat::R16G16B16A16Typeless
+                | TexFormat::R32G32Typeless
+                | TexFormat::R32G8X24Typeless
+                | TexFormat::R10G10B10A2Typeless
+                | TexFormat::R8G8B8A8Typeless
+                | TexFormat::R16G16Typeless
+                | TexFormat::R32Typeless
+                | TexFormat::R24G8Typeless
+                | TexFormat::R8G8Typeless
+                | TexFormat::R16Typeless
+                | TexFormat::R8Typeless
+                | TexFormat::Bc1Typeless
+                | TexFormat::Bc2Typeless
+                | TexFormat::Bc3Typeless
+                | TexFormat::Bc4Typeless
+                | TexFormat::Bc5Typeless
+                | TexFormat::Bc6hTypeless
+                | TexFormat::Bc7Typeless
+                | TexFormat::B8G8R8A8Typeless
+                | TexFormat::B8G8R8X8Typeless
+        )
+    }
+
+    /// Whether channels can store negative values.
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            TexFormat::R32G32B32A32Float
+                | TexFormat::R32G32B32A32Sint
+                | TexFormat::R32G32B32Float
+                | TexFormat::R32G32B32Sint
+                | TexFormat::R16G16B16A16Float
+                | TexFormat::R16G16B16A16Snorm
+                | TexFormat::R16G16B16A16Sint
+                | TexFormat::R32G32Float
+                | TexFormat::R32G32Sint
+                | TexFormat::D32FloatS8X24Uint
+                | TexFormat::R32FloatX8X24Typeless
+                | TexFormat::R8G8B8A8Snorm
+                | TexFormat::R8G8B8A8Sint
+                | TexFormat::R16G16Float
+                | TexFormat::R16G16Snorm
+                | TexFormat::R16G16Sint
+                | TexFormat::D32Float
+                | TexFormat::R32Float
+                | TexFormat::R32Sint
+                | TexFormat::R8G8Snorm
+                | TexFormat::R8G8Sint
+                | TexFormat::R16Float
+                | TexFormat::R16Snorm
+                | TexFormat::R16Sint
+                | TexFormat::R8Snorm
+                | TexFormat::R8Sint
+                | TexFormat::Bc4Snorm
+                | TexFormat::Bc5Snorm
+                | TexFormat::Bc6hSF16
+        )
+    }
+
+    /// Whether the format stores high dynamic range values, beyond `0.0..=1.0`.
+    pub fn is_hdr(&self) -> bool {
+        matches!(
+            self,
+            TexFormat::R32G32B32A32Float
+                | TexFormat::R32G32B32Float
+                | TexFormat::R16G16B16A16Float
+                | TexFormat::R32G32Float
+                | TexFormat::R11G11B10Float
+                | TexFormat::R16G16Float
+                | TexFormat::R32Float
+                | TexFormat::R16Float
+                | TexFormat::R9G9B9E5Sharedexp
+                | TexFormat::Bc6hTypeless
+                | TexFormat::Bc6hUF16
+                | TexFormat::Bc6hSF16
+        )
+    }
+
+    /// Size in bytes of a row of blocks of mipmap `level`, without padding.
+    pub fn row_pitch(&self, width: u32, level: u32) -> Option<u32> {
+        let (block_width, _) = self.block_dimensions();
+        let width = u32::max(1, width >> level);
+        width
+            .div_ceil(block_width)
+            .checked_mul(self.bytes_per_block()?)
+    }
+
+    /// Size in bytes of mipmap `level`, including all depth slices, without padding.
+    pub fn mip_size(&self, width: u32, height: u32, depth: u32, level: u32) -> Option<usize> {
+        let (_, block_height) = self.block_dimensions();
+        let rows = u32::max(1, height >> level).div_ceil(block_height);
+        let slices = u32::max(1, depth >> level);
+        (self.row_pitch(width, level)? as usize)
+            .checked_mul(rows as usize)?
+            .checked_mul(slices as usize)
+    }
+}
+
+/// DXGI format mapping, used for DDS files.
+impl TexFormat {
+    /// Get the matching DXGI format.
+    ///
+    /// ASTC formats and the placeholders `ViaExtension` and `ForceUint` have no
+    /// DXGI equivalent.
+    pub fn to_dxgi(&self) -> Result<DxgiFormat> {
+        let format = match self {
+            TexFormat::R32G32B32A32Typeless => DxgiFormat::R32G32B32A32_Typeless,
+            TexFormat::R32G32B32A32Float => DxgiFormat::R32G32B32A32_Float,
+            TexFormat::R32G32B32A32Uint => DxgiFormat::R32G32B32A32_UInt,
+            TexFormat::R32G32B32A32Sint => DxgiFormat::R32G32B32A32_SInt,
+            TexFormat::R32G32B32Typeless => DxgiFormat::R32G32B32_Typeless,
+            TexFormat::R32G32B32Float => DxgiFormat::R32G32B32_Float,
+            TexFormat::R32G32B32Uint => DxgiFormat::R32G32B32_UInt,
+            TexFormat::R32G32B32Sint => DxgiFormat::R32G32B32_SInt,
+            TexFormat::R16G16B16A16Typeless => DxgiFormat::R16G16B16A16_Typeless,
+            TexFormat::R16G16B16A16Float => DxgiFormat::R16G16B16A16_Float,
+            TexFormat::R16G16B16A16Unorm => DxgiFormat::R16G16B16A16_UNorm,
+            TexFormat::R16G16B16A16Uint => DxgiFormat::R16G16B16A16_UInt,
+            TexFormat::R16G16B16A16Snorm => DxgiFormat::R16G16B16A16_SNorm,
+            TexFormat::R16G16B16A16Sint => DxgiFormat::R16G16B16A16_SInt,
+            TexFormat::R32G32Typeless => DxgiFormat::R32G32_Typeless,
+            TexFormat::R32G32Float => DxgiFormat::R32G32_Float,
+            TexFormat::R32G32Uint => DxgiFormat::R32G32_UInt,
+            TexFormat::R32G32Sint => DxgiFormat::R32G32_SInt,
+            TexFormat::R32G8X24Typeless => DxgiFormat::R32G8X24_Typeless,
+            TexFormat::D32FloatS8X24Uint => DxgiFormat::D32_Float_S8X24_UInt,
+            TexFormat::R32FloatX8X24Typeless => DxgiFormat::R32_Float_X8X24_Typeless,
+            TexFormat::X32TypelessG8X24Uint => DxgiFormat::X32_Typeless_G8X24_UInt,
+            TexFormat::R10G10B10A2Typeless => DxgiFormat::R10G10B10A2_Typeless,
+            TexFormat::R10G10B10A2Unorm => DxgiFormat::R10G10B10A2_UNorm,
+            TexFormat::R10G10B10A2Uint => DxgiFormat::R10G10B10A2_UInt,
+            TexFormat::R11G11B10Float => DxgiFormat::R11G11B10_Float,
+            TexFormat::R8G8B8A8Typeless => DxgiFormat::R8G8B8A8_Typeless,
+            TexFormat::R8G8B8A8Unorm => DxgiFormat::R8G8B8A8_UNorm,
+            TexFormat::R8G8B8A8UnormSrgb => DxgiFormat::R8G8B8A8_UNorm_sRGB,
+            TexFormat::R8G8B8A8Uint => DxgiFormat::R8G8B8A8_UInt,
+            TexFormat::R8G8B8A8Snorm => DxgiFormat::R8G8B8A8_SNorm,
+            TexFormat::R8G8B8A8Sint => DxgiFormat::R8G8B8A8_SInt,
+            TexFormat::R16G16Typeless => DxgiFormat::R16G16_Typeless,
+            TexFormat::R16G16Float => DxgiFormat::R16G16_Float,
+            TexFormat::R16G16Unorm => DxgiFormat::R16G16_UNorm,
+            TexFormat::R16G16Uint => DxgiFormat::R16G16_UInt,
+            TexFormat::R16G16Snorm => DxgiFormat::R16G16_SNorm,
+            TexFormat::R16G16Sint => DxgiFormat::R16G16_SInt,
+            TexFormat::R32Typeless => DxgiFormat::R32_Typeless,
+            TexFormat::D32Float => DxgiFormat::D32_Float,
+            TexFormat::R32Float => DxgiFormat::R32_Float,
+            TexFormat::R32Uint => DxgiFormat::R32_UInt,
+            TexFormat::R32Sint => DxgiFormat::R32_SInt,
+            TexFormat::R24G8Typeless => DxgiFormat::R24G8_Typeless,
+            TexFormat::D24UnormS8Uint => DxgiFormat::D24_UNorm_S8_UInt,
+            TexFormat::R24UnormX8Typeless => DxgiFormat::R24_UNorm_X8_Typeless,
+            TexFormat::X24TypelessG8Uint => DxgiFormat::X24_Typeless_G8_UInt,
+            TexFormat::R8G8Typeless => DxgiFormat::R8G8_Typeless,
+            TexFormat::R8G8Unorm => DxgiFormat::R8G8_UNorm,
+            TexFormat::R8G8Uint => DxgiFormat::R8G8_UInt,
+            TexFormat::R8G8Snorm => DxgiFormat::R8G8_SNorm,
+            TexFormat::R8G8Sint => DxgiFormat::R8G8_SInt,
+            TexFormat::R16Typeless => DxgiFormat::R16_Typeless,
+            TexFormat::R16Float => DxgiFormat::R16_Float,
+            TexFormat::D16Unorm => DxgiFormat::D16_UNorm,
+            TexFormat::R16Unorm => DxgiFormat::R16_UNorm,
+            TexFormat::R16Uint => DxgiFormat::R16_UInt,
+            TexFormat::R16Snorm => DxgiFormat::R16_SNorm,
+            TexFormat::R16Sint => DxgiFormat::R16_SInt,
+            TexFormat::R8Typeless => DxgiFormat::R8_Typeless,
+            TexFormat::R8Unorm => DxgiFormat::R8_UNorm,
+            TexFormat::R8Uint => DxgiFormat::R8_UInt,
+            TexFormat::R8Snorm => DxgiFormat::R8_SNorm,
+            TexFormat::R8Sint => DxgiFormat::R8_SInt,
+            TexFormat::A8Unorm => DxgiFormat::A8_UNorm,
+            TexFormat::R1Unorm => DxgiFormat::R1_UNorm,
+            TexFormat::R9G9B9E5Sharedexp => DxgiFormat::R9G9B9E5_SharedExp,
+            TexFormat::R8G8B8G8Unorm => DxgiFormat::R8G8_B8G8_UNorm,
+            TexFormat::G8R8G8B8Unorm => DxgiFormat::G8R8_G8B8_UNorm,
+            TexFormat::Bc1Typeless => DxgiFormat::BC1_Typeless,
+            TexFormat::Bc1Unorm => DxgiFormat::BC1_UNorm,
+            TexFormat::Bc1UnormSrgb => DxgiFormat::BC1_UNorm_sRGB,
+            TexFormat::Bc2Typeless => DxgiFormat::BC2_Typeless,
+            TexFormat::Bc2Unorm => DxgiFormat::BC2_UNorm,
+            TexFormat::Bc2UnormSrgb => DxgiFormat::BC2_UNorm_sRGB,
+            TexFormat::Bc3Typeless => DxgiFormat::BC3_Typeless,
+            TexFormat::Bc3Unorm => DxgiFormat::BC3_UNorm,
+            TexFormat::Bc3UnormSrgb => DxgiFormat::BC3_UNorm_sRGB,
+            TexFormat::Bc4Typeless => DxgiFormat::BC4_Typeless,
+            TexFormat::Bc4Unorm => DxgiFormat::BC4_UNorm,
+            TexFormat::Bc4Snorm => DxgiFormat::BC4_SNorm,
+            TexFormat::Bc5Typeless => DxgiFormat::BC5_Typeless,
+            TexFormat::Bc5Unorm => DxgiFormat::BC5_UNorm,
+            TexFormat::Bc5Snorm => DxgiFormat::BC5_SNorm,
+            TexFormat::B5G6R5Unorm => DxgiFormat::B5G6R5_UNorm,
+            TexFormat::B5G5R5A1Unorm => DxgiFormat::B5G5R5A1_UNorm,
+            TexFormat::B8G8R8A8Unorm => DxgiFormat::B8G8R8A8_UNorm,
+            TexFormat::B8G8R8X8Unorm => DxgiFormat::B8G8R8X8_UNorm,
+            TexFormat::R10G10B10xrBiasA2Unorm => DxgiFormat::R10G10B10_XR_Bias_A2_UNorm,
+            TexFormat::B8G8R8A8Typeless => DxgiFormat::B8G8R8A8_Typeless,
+            TexFormat::B8G8R8A8UnormSrgb => DxgiFormat::B8G8R8A8_UNorm_sRGB,
+            TexFormat::B8G8R8X8Typeless => DxgiFormat::B8G8R8X8_Typeless,
+            TexFormat::B8G8R8X8UnormSrgb => DxgiFormat::B8G8R8X8_UNorm_sRGB,
+            TexFormat::Bc6hTypeless => DxgiFormat::BC6H_Typeless,
+            TexFormat::Bc6hUF16 => DxgiFormat::BC6H_UF16,
+            TexFormat::Bc6hSF16 => DxgiFormat::BC6H_SF16,
+            TexFormat::Bc7Typeless => DxgiFormat::BC7_Typeless,
+            TexFormat::Bc7Unorm => DxgiFormat::BC7_UNorm,
+            TexFormat::Bc7UnormSrgb => DxgiFormat::BC7_UNorm_sRGB,
+            _ => return Err(Error::NoDxgiFormat(*self)),
+        };
+        Ok(format)
+    }
+
+    /// Get the matching Tex format of a DXGI format.
+    pub fn from_dxgi(format: DxgiFormat) -> Result<Self> {
+        let format = match format {
+            DxgiFormat::R32G32B32A32_Typeless => TexFormat::R32G32B32A32Typeless,
+            DxgiFormat::R32G32B32A32_Float => TexFormat::R32G32B32A32Float,
+            DxgiFormat::R32G32B32A32_UInt => TexFormat::R32G32B32A32Uint,
+            DxgiFormat::R32G32B32A32_SInt => TexFormat::R32G32B32A32Sint,
+            DxgiFormat::R32G32B32_Typeless => TexFormat::R32G32B32Typeless,
+            DxgiFormat::R32G32B32_Float => TexFormat::R32G32B32Float,
+            DxgiFormat::R32G32B32_UInt => TexFormat::R32G32B32Uint,
+            DxgiFormat::R32G32B32_SInt => TexFormat::R32G32B32Sint,
+            DxgiFormat::R16G16B16A16_Typeless => TexFormat::R16G16B16A16Typeless,
+            DxgiFormat::R16G16B16A16_Float => TexFormat::R16G16B16A16Float,
+            DxgiFormat::R16G16B16A16_UNorm => TexFormat::R16G16B16A16Unorm,
+            DxgiFormat::R16G16B16A16_UInt => TexFormat::R16G16B16A16Uint,
+            DxgiFormat::R16G16B16A16_SNorm => TexFormat::R16G16B16A16Snorm,
+            DxgiFormat::R16G16B16A16_SInt => TexFormat::R16G16B16A16Sint,
+            DxgiFormat::R32G32_Typeless => TexFormat::R32G32Typeless,
+            DxgiFormat::R32G32_Float => TexFormat::R32G32Float,
+            DxgiFormat::R32G32_UInt => TexFormat::R32G32Uint,
+            DxgiFormat::R32G32_SInt => TexFormat::R32G32Sint,
+            DxgiFormat::R32G8X24_Typeless => TexFormat::R32G8X24Typeless,
+            DxgiFormat::D32_Float_S8X24_UInt => TexFormat::D32FloatS8X24Uint,
+            DxgiFormat::R32_Float_X8X24_Typeless => TexFormat::R32FloatX8X24Typeless,
+            DxgiFormat::X32_Typeless_G8X24_UInt => TexFormat::X32TypelessG8X24Uint,
+            DxgiFormat::R10G10B10A2_Typeless => TexFormat::R10G10B10A2Typeless,
+            DxgiFormat::R10G10B10A2_UNorm => TexFormat::R10G10B10A2Unorm,
+            DxgiFormat::R10G10B10A2_UInt => TexFormat::R10G10B10A2Uint,
+            DxgiFormat::R11G11B10_Float => TexFormat::R11G11B10Float,
+            DxgiFormat::R8G8B8A8_Typeless => TexFormat::R8G8B8A8Typeless,
+            DxgiFormat::R8G8B8A8_UNorm => TexFormat::R8G8B8A8Unorm,
+            DxgiFormat::R8G8B8A8_UNorm_sRGB => TexFormat::R8G8B8A8UnormSrgb,
+            DxgiFormat::R8G8B8A8_UInt => TexFormat::R8G8B8A8Uint,
+            DxgiFormat::R8G8B8A8_SNorm => TexFormat::R8G8B8A8Snorm,
+            DxgiFormat::R8G8B8A8_SInt => TexFormat::R8G8B8A8Sint,
+            DxgiFormat::R16G16_Typeless => TexFormat::R16G16Typeless,
+            DxgiFormat::R16G16_Float => TexFormat::R16G16Float,
+            DxgiFormat::R16G16_UNorm => TexFormat::R16G16Unorm,
+            DxgiFormat::R16G16_UInt => TexFormat::R16G16Uint,
+            DxgiFormat::R16G16_SNorm => TexFormat::R16G16Snorm,
+            DxgiFormat::R16G16_SInt => TexFormat::R16G16Sint,
+            DxgiFormat::R32_Typeless => TexFormat::R32Typeless,
+            DxgiFormat::D32_Float => TexFormat::D32Float,
+            DxgiFormat::R32_Float => TexFormat::R32Float,
+            DxgiFormat::R32_UInt => TexFormat::R32Uint,
+            DxgiFormat::R32_SInt => TexFormat::R32Sint,
+            DxgiFormat::R24G8_Typeless => TexFormat::R24G8Typeless,
+            DxgiFormat::D24_UNorm_S8_UInt => TexFormat::D24UnormS8Uint,
+            DxgiFormat::R24_UNorm_X8_Typeless => TexFormat::R24UnormX8Typeless,
+            DxgiFormat::X24_Typeless_G8_UInt => TexFormat::X24TypelessG8Uint,
+            DxgiFormat::R8G8_Typeless => TexFormat::R8G8Typeless,
+            DxgiFormat::R8G8_UNorm => TexFormat::R8G8Unorm,
+            DxgiFormat::R8G8_UInt => TexFormat::R8G8Uint,
+            DxgiFormat::R8G8_SNorm => TexFormat::R8G8Snorm,
+            DxgiFormat::R8G8_SInt => TexFormat::R8G8Sint,
+            DxgiFormat::R16_Typeless => TexFormat::R16Typeless,
+            DxgiFormat::R16_Float => TexFormat::R16Float,
+            DxgiFormat::D16_UNorm => TexFormat::D16Unorm,
+            DxgiFormat::R16_UNorm => TexFormat::R16Unorm,
+            DxgiFormat::R16_UInt => TexFormat::R16Uint,
+            DxgiFormat::R16_SNorm => TexFormat::R16Snorm,
+            DxgiFormat::R16_SInt => TexFormat::R16Sint,
+            DxgiFormat::R8_Typeless => TexFormat::R8Typeless,
+            DxgiFormat::R8_UNorm => TexFormat::R8Unorm,
+            DxgiFormat::R8_UInt => TexFormat::R8Uint,
+            DxgiFormat::R8_SNorm => TexFormat::R8Snorm,
+            DxgiFormat::R8_SInt => TexFormat::R8Sint,
+            DxgiFormat::A8_UNorm => TexFormat::A8Unorm,
+            DxgiFormat::R1_UNorm => TexFormat::R1Unorm,
+            DxgiFormat::R9G9B9E5_SharedExp => TexFormat::R9G9B9E5Sharedexp,
+            DxgiFormat::R8G8_B8G8_UNorm => TexFormat::R8G8B8G8Unorm,
+            DxgiFormat::G8R8_G8B8_UNorm => TexFormat::G8R8G8B8Unorm,
+            DxgiFormat::BC1_Typeless => TexFormat::Bc1Typeless,
+            DxgiFormat::BC1_UNorm => TexFormat::Bc1Unorm,
+            DxgiFormat::BC1_UNorm_sRGB => TexFormat::Bc1UnormSrgb,
+            DxgiFormat::BC2_Typeless => TexFormat::Bc2Typeless,
+            DxgiFormat::BC2_UNorm => TexFormat::Bc2Unorm,
+            DxgiFormat::BC2_UNorm_sRGB => TexFormat::Bc2UnormSrgb,
+            DxgiFormat::BC3_Typeless => TexFormat::Bc3Typeless,
+            DxgiFormat::BC3_UNorm => TexFormat::Bc3Unorm,
+            DxgiFormat::BC3_UNorm_sRGB => TexFormat::Bc3UnormSrgb,
+            DxgiFormat::BC4_Typeless => TexFormat::Bc4Typeless,
+            DxgiFormat::BC4_UNorm => TexFormat::Bc4Unorm,
+            DxgiFormat::BC4_SNorm => TexFormat::Bc4Snorm,
+            DxgiFormat::BC5_Typeless => TexFormat::Bc5Typeless,
+            DxgiFormat::BC5_UNorm => TexFormat::Bc5Unorm,
+            DxgiFormat::BC5_SNorm => TexFormat::Bc5Snorm,
+            DxgiFormat::B5G6R5_UNorm => TexFormat::B5G6R5Unorm,
+            DxgiFormat::B5G5R5A1_UNorm => TexFormat::B5G5R5A1Unorm,
+            DxgiFormat::B8G8R8A8_UNorm => TexFormat::B8G8R8A8Unorm,
+            DxgiFormat::B8G8R8X8_UNorm => TexFormat::B8G8R8X8Unorm,
+            DxgiFormat::R10G10B10_XR_Bias_A2_UNorm => TexFormat::R10G10B10xrBiasA2Unorm,
+            DxgiFormat::B8G8R8A8_Typeless => TexFormat::B8G8R8A8Typeless,
+            DxgiFormat::B8G8R8A8_UNorm_sRGB => TexFormat::B8G8R8A8UnormSrgb,
+            DxgiFormat::B8G8R8X8_Typeless => TexFormat::B8G8R8X8Typeless,
+            DxgiFormat::B8G8R8X8_UNorm_sRGB => TexFormat::B8G8R8X8UnormSrgb,
+            DxgiFormat::BC6H_Typeless => TexFormat::Bc6hTypeless,
+            DxgiFormat::BC6H_UF16 => TexFormat::Bc6hUF16,
+            DxgiFormat::BC6H_SF16 => TexFormat::Bc6hSF16,
+            DxgiFormat::BC7_Typeless => TexFormat::Bc7Typeless,
+            DxgiFormat::BC7_UNorm => TexFormat::Bc7Unorm,
+            DxgiFormat::BC7_UNorm_sRGB => TexFormat::Bc7UnormSrgb,
+            _ => return Err(Error::UnsupportedDxgiFormat(format as u32)),
+        };
+        Ok(format)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -263,5 +835,97 @@ mod tests {
         assert!(TexFormat::R8G8B8G8Unorm.is_rgb());
         assert!(TexFormat::R16G16B16A16Sint.is_rgb());
         assert!(TexFormat::R16G16B16A16Snorm.is_rgb());
+
+        assert!(!TexFormat::B8G8R8A8Unorm.is_bc());
+        assert!(!TexFormat::ViaExtension.is_astc());
+        assert!(!TexFormat::ForceUint.is_astc());
+    }
+
+    #[test]
+    fn test_format_metadata() {
+        assert_eq!(TexFormat::Bc7UnormSrgb.block_dimensions(), (4, 4));
+        assert_eq!(TexFormat::Astc4x4Unorm.block_dimensions(), (4, 4));
+        assert_eq!(TexFormat::Astc10x8UnormSrgb.block_dimensions(), (10, 8));
+        assert_eq!(TexFormat::Astc12x12Typeless.block_dimensions(), (12, 12));
+        assert_eq!(TexFormat::R8G8B8A8Unorm.block_dimensions(), (1, 1));
+
+        assert_eq!(TexFormat::Bc1Unorm.bytes_per_block(), Some(8));
+        assert_eq!(TexFormat::Bc7Unorm.bytes_per_block(), Some(16));
+        assert_eq!(TexFormat::R16G16B16A16Float.bytes_per_block(), Some(8));
+        assert_eq!(TexFormat::ViaExtension.bytes_per_block(), None);
+
+        assert_eq!(TexFormat::Bc5Unorm.channel_count(), 2);
+        assert_eq!(TexFormat::B8G8R8X8Unorm.channel_count(), 3);
+        assert_eq!(TexFormat::R8G8B8A8Unorm.channel_count(), 4);
+
+        assert!(TexFormat::Astc6x6UnormSrgb.is_srgb());
+        assert!(!TexFormat::Astc6x6Unorm.is_srgb());
+        assert!(TexFormat::Astc6x6Typeless.is_typeless());
+        assert!(TexFormat::Bc7Typeless.is_typeless());
+        assert!(!TexFormat::R24UnormX8Typeless.is_typeless());
+        assert!(TexFormat::Bc6hSF16.is_signed() && TexFormat::Bc6hSF16.is_hdr());
+        assert!(!TexFormat::Bc6hUF16.is_signed() && TexFormat::Bc6hUF16.is_hdr());
+        assert!(!TexFormat::R8G8B8A8UnormSrgb.is_hdr());
+    }
+
+    #[test]
+    fn test_format_mip_size() {
+        let format = TexFormat::Bc7UnormSrgb;
+        assert_eq!(format.row_pitch(1024, 0), Some(4096));
+        assert_eq!(format.row_pitch(1024, 10), Some(16));
+        assert_eq!(format.mip_size(1024, 2048, 1, 0), Some(1024 * 2048));
+        assert_eq!(format.mip_size(1024, 2048, 1, 11), Some(16));
+
+        let format = TexFormat::Astc6x6Unorm;
+        assert_eq!(format.row_pitch(100, 0), Some(17 * 16));
+        assert_eq!(format.mip_size(100, 100, 1, 0), Some(17 * 17 * 16));
+
+        let format = TexFormat::R8G8B8A8Unorm;
+        assert_eq!(format.mip_size(8, 8, 4, 1), Some(4 * 4 * 2 * 4));
+        assert_eq!(TexFormat::ForceUint.mip_size(8, 8, 1, 0), None);
+    }
+
+    #[test]
+    fn test_format_dxgi() {
+        use strum::IntoEnumIterator as _;
+
+        assert_eq!(
+            TexFormat::Bc7UnormSrgb.to_dxgi().unwrap(),
+            DxgiFormat::BC7_UNorm_sRGB
+        );
+        assert_eq!(
+            TexFormat::from_dxgi(DxgiFormat::BC7_UNorm_sRGB).unwrap(),
+            TexFormat::Bc7UnormSrgb
+        );
+        assert!(TexFormat::Astc4x4Unorm.to_dxgi().is_err());
+        assert!(TexFormat::ViaExtension.to_dxgi().is_err());
+        assert!(TexFormat::ForceUint.to_dxgi().is_err());
+        assert!(TexFormat::from_dxgi(DxgiFormat::NV12).is_err());
+
+        // every DXGI mapping round trips with the same value
+        for format in TexFormat::iter() {
+            if let Ok(dxgi_format) = format.to_dxgi() {
+                assert_eq!(dxgi_format as u32, format as u32);
+                assert_eq!(TexFormat::from_dxgi(dxgi_format).unwrap(), format);
+            } else {
+                assert!(format.is_astc() || format.bytes_per_block().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_pitch_matches_dxgi() {
+        use ddsfile::DataFormat as _;
+        use strum::IntoEnumIterator as _;
+
+        for format in TexFormat::iter() {
+            let Ok(dxgi_format) = format.to_dxgi() else {
+                continue;
+            };
+            let Some(pitch) = dxgi_format.get_pitch(100) else {
+                continue;
+            };
+            assert_eq!(format.row_pitch(100, 0), Some(pitch), "{format:?}");
+        }
     }
 }
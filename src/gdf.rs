@@ -1,14 +1,35 @@
 //! GDeflate compression and decompression.
 //!
-//! Reference: https://github.com/microsoft/DirectStorage/blob/main/GDeflate/GDeflate/GDeflateCompress.cpp
+//! GDeflate streams start with an 8-byte header and a tile offset table,
+//! followed by independently compressed 64 KiB tiles.
+//! Whole buffers go through [`GDfDecompressor`] and [`GDfCompressor`],
+//! and [`GDfReader`] and [`GDfWriter`] adapt them to [`io::Read`] and [`io::Write`].
+//! [`with_decompressor`] and [`with_compressor`] reuse codecs cached per thread.
+//!
+//! Reference: <https://github.com/microsoft/DirectStorage/blob/main/GDeflate/GDeflate/GDeflateCompress.cpp>
 
-use std::io;
+use std::cell::{Cell, RefCell};
+use std::io::{self, Read as _};
+use std::ops::Range;
 
 use byteorder::{LE, ReadBytesExt};
-use gdeflate::sys;
 
 use crate::macros::BitField as _;
 
+#[cfg(not(any(feature = "libdeflate", feature = "rust-gdeflate")))]
+compile_error!("GDeflate needs the `libdeflate` or `rust-gdeflate` feature.");
+
+// the backend not in use is still built for tests, to compare both
+#[cfg(all(feature = "libdeflate", any(test, not(feature = "rust-gdeflate"))))]
+mod libdeflate;
+#[cfg(any(test, feature = "rust-gdeflate"))]
+mod pure;
+
+#[cfg(not(feature = "rust-gdeflate"))]
+use libdeflate as backend;
+#[cfg(feature = "rust-gdeflate")]
+use pure as backend;
+
 type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, thiserror::Error)]
@@ -41,6 +62,70 @@ pub enum CompressionError {
     CompressionFailed,
 }
 
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::IO(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// GDeflate compression level, from storing only (`None`) to the slowest
+/// and smallest output (`Level12`).
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionLevel {
+    None = 0,
+    Level1 = 1,
+    Level2 = 2,
+    Level3 = 3,
+    Level4 = 4,
+    Level5 = 5,
+    Level6 = 6,
+    Level7 = 7,
+    Level8 = 8,
+    Level9 = 9,
+    Level10 = 10,
+    Level11 = 11,
+    Level12 = 12,
+}
+
+/// GDeflate tile size, stored as an index in the stream header.
+///
+/// The DirectStorage stream format only defines index 1, 64 KiB tiles,
+/// so streams with other indices are rejected as bad data.
+#[repr(u32)]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileSize {
+    /// 64 KiB tiles, index 1.
+    #[default]
+    KiB64 = 1,
+}
+
+impl TileSize {
+    /// Tile size of a stream header index.
+    pub fn from_index(index: u32) -> Option<TileSize> {
+        match index {
+            1 => Some(TileSize::KiB64),
+            _ => None,
+        }
+    }
+
+    /// Index stored in the stream header.
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+
+    /// Size of a tile in bytes.
+    pub fn bytes(self) -> usize {
+        match self {
+            TileSize::KiB64 => KDEFAULT_TILE_SIZE,
+        }
+    }
+}
+
 // partial from https://github.com/c-ola/libdeflater
 // modified
 
@@ -59,23 +144,24 @@ struct TileStream {
 }
 
 impl TileStream {
-    pub fn new(uncompressed_size: usize) -> TileStream {
-        let mut num_tiles = (uncompressed_size / KDEFAULT_TILE_SIZE).try_into().unwrap();
-        let last_tile_size = (uncompressed_size - num_tiles as usize * KDEFAULT_TILE_SIZE)
-            .try_into()
-            .unwrap();
-        num_tiles += if last_tile_size != 0 { 1 } else { 0 };
-        TileStream {
+    /// Header of a stream of `uncompressed_size` bytes.
+    ///
+    /// Fails if the data needs more tiles than the `u16` tile count holds.
+    pub fn new(uncompressed_size: usize, tile_size: TileSize) -> Result<TileStream> {
+        let num_tiles = u16::try_from(uncompressed_size.div_ceil(tile_size.bytes()))
+            .map_err(|_| CompressionError::CompressionFailed)?;
+        let last_tile_size = (uncompressed_size % tile_size.bytes()) as u32;
+        Ok(TileStream {
             id: K_GDEFLATE_ID,
             magic: K_GDEFLATE_ID ^ 0xFF,
-            tile_size_idx: 1,
+            tile_size_idx: tile_size.index(),
             num_tiles,
             last_tile_size,
             reserv1: 0,
-        }
+        })
     }
 
-    pub fn from<W: io::Read + io::Seek>(data: &mut W) -> Result<TileStream> {
+    pub fn from<R: io::Read>(data: &mut R) -> Result<TileStream> {
         let id = data.read_u8()?;
         let magic = data.read_u8()?;
         let num_tiles = data.read_u16::<LE>()?;
@@ -91,233 +177,905 @@ impl TileStream {
         })
     }
 
+    /// Tile size in bytes, from the header index.
+    ///
+    /// Unknown indices fall back to the default size and fail [`TileStream::is_valid`].
+    fn tile_size(&self) -> usize {
+        TileSize::from_index(self.tile_size_idx).map_or(KDEFAULT_TILE_SIZE, TileSize::bytes)
+    }
+
     pub fn get_uncompressed_size(&self) -> usize {
-        self.num_tiles as usize * KDEFAULT_TILE_SIZE
+        self.num_tiles as usize * self.tile_size()
             - if self.last_tile_size == 0 {
                 0
             } else {
-                KDEFAULT_TILE_SIZE - self.last_tile_size as usize
+                self.tile_size() - self.last_tile_size as usize
             }
     }
 
+    /// Uncompressed size of a tile.
+    fn tile_uncompressed_size(&self, tile_index: usize) -> usize {
+        if tile_index + 1 == self.num_tiles as usize && self.last_tile_size != 0 {
+            self.last_tile_size as usize
+        } else {
+            self.tile_size()
+        }
+    }
+
     pub fn is_valid(&self) -> bool {
-        self.id == self.magic ^ 0xFF && self.id == K_GDEFLATE_ID
+        self.id == self.magic ^ 0xFF
+            && self.id == K_GDEFLATE_ID
+            && TileSize::from_index(self.tile_size_idx).is_some()
+            && self.last_tile_size as usize <= self.tile_size()
+            && (self.num_tiles > 0 || self.last_tile_size == 0)
     }
 }
 
-pub struct GDfDecompressor(*mut sys::libdeflate_gdeflate_decompressor);
+/// Whether `data` starts with a GDeflate stream header.
+pub fn is_gdeflate(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] == K_GDEFLATE_ID && data[1] == K_GDEFLATE_ID ^ 0xFF
+}
 
-impl GDfDecompressor {
-    pub fn new() -> Result<GDfDecompressor> {
-        let decompressor = unsafe { sys::libdeflate_alloc_gdeflate_decompressor() };
-        if decompressor.is_null() {
-            Err(DecompressionError::DecompressorCreationFailed)?
+/// Get the uncompressed size of GDeflate data from its stream header,
+/// without decompressing it.
+pub fn uncompressed_size(in_data: &[u8]) -> Result<usize> {
+    let tile_stream = TileStream::from(&mut io::Cursor::new(in_data))?;
+    if !tile_stream.is_valid() {
+        Err(DecompressionError::BadData)?
+    }
+    Ok(tile_stream.get_uncompressed_size())
+}
+
+/// Size of the stream header in bytes.
+const HEADER_SIZE: usize = size_of::<u64>();
+
+/// Read the tile offset table following the stream header.
+fn read_tile_table<R: io::Read>(tile_stream: &TileStream, reader: &mut R) -> Result<Vec<usize>> {
+    (0..tile_stream.num_tiles)
+        .map(|_| Ok(reader.read_u32::<LE>()? as usize))
+        .collect()
+}
+
+/// Byte ranges of the compressed tiles, relative to the end of the tile table.
+fn tile_ranges(tile_table: &[usize]) -> Result<Vec<Range<usize>>> {
+    // the first entry holds the size of the last tile,
+    // the others hold the offset of each tile
+    let num_tiles = tile_table.len();
+    let mut ranges = Vec::with_capacity(num_tiles);
+    for tile_index in 0..num_tiles {
+        let start = if tile_index > 0 {
+            tile_table[tile_index]
+        } else {
+            0
+        };
+        let end = if tile_index + 1 < num_tiles {
+            tile_table[tile_index + 1]
         } else {
-            Ok(Self(decompressor))
+            start
+                .checked_add(tile_table[0])
+                .ok_or(DecompressionError::BadData)?
+        };
+        if end < start {
+            Err(DecompressionError::BadData)?;
         }
+        ranges.push(start..end);
+    }
+    Ok(ranges)
+}
+
+/// Split GDeflate data into its compressed tiles.
+///
+/// Every tile range is checked against the input, so corrupted headers
+/// or truncated data return [`DecompressionError::BadData`].
+fn split_tiles<'a>(tile_stream: &TileStream, in_data: &'a [u8]) -> Result<Vec<&'a [u8]>> {
+    let table_size = tile_stream.num_tiles as usize * size_of::<u32>();
+    let (mut table, data) = in_data
+        .get(HEADER_SIZE..)
+        .filter(|rest| rest.len() >= table_size)
+        .map(|rest| rest.split_at(table_size))
+        .ok_or(DecompressionError::BadData)?;
+    let tile_table = read_tile_table(tile_stream, &mut table)?;
+
+    tile_ranges(&tile_table)?
+        .into_iter()
+        .map(|range| Ok(data.get(range).ok_or(DecompressionError::BadData)?))
+        .collect()
+}
+
+/// Validate GDeflate data and split it into its tiles.
+fn prepare_decompress(in_data: &[u8]) -> Result<(TileStream, Vec<&[u8]>)> {
+    let tile_stream = TileStream::from(&mut io::Cursor::new(in_data))?;
+    if !tile_stream.is_valid() {
+        Err(DecompressionError::BadData)?;
+    }
+    let tiles = split_tiles(&tile_stream, in_data)?;
+    Ok((tile_stream, tiles))
+}
+
+/// Check that a tile decompressed to its expected size.
+fn check_tile_size(tile_stream: &TileStream, tile_index: usize, out_nbytes: usize) -> Result<()> {
+    if out_nbytes != tile_stream.tile_uncompressed_size(tile_index) {
+        Err(DecompressionError::BadData)?;
+    }
+    Ok(())
+}
+
+/// Upper bound of the compressed size of a tile, as in
+/// `libdeflate_gdeflate_compress_bound`: uncompressed blocks of at most
+/// 10000 bytes with 5 bytes of overhead each, end padding, and one packet
+/// for each of the 32 bit streams.
+fn max_compressed_tile_size(tile_size: usize) -> usize {
+    const MIN_BLOCK_LENGTH: usize = 10000;
+    const OUTPUT_END_PADDING: usize = 8;
+    const STREAM_PACKETS_SIZE: usize = 32 * size_of::<u32>();
+    5 * tile_size.div_ceil(MIN_BLOCK_LENGTH)
+        + tile_size
+        + 1
+        + OUTPUT_END_PADDING
+        + STREAM_PACKETS_SIZE
+}
+
+/// Decompress every tile of GDeflate data with `decompress_tile`.
+fn decompress_with(
+    in_data: &[u8],
+    decompress_tile: impl FnMut(&[u8], &mut [u8]) -> Result<usize>,
+) -> Result<Vec<u8>> {
+    // only allocate once every tile is known to lie inside the input
+    let (tile_stream, tiles) = prepare_decompress(in_data)?;
+    let mut out_data = vec![0u8; tile_stream.get_uncompressed_size()];
+    decompress_tiles(&tile_stream, tiles, &mut out_data, decompress_tile)?;
+    Ok(out_data)
+}
+
+/// Decompress every tile of GDeflate data into the start of `out_data`
+/// with `decompress_tile`, returning the decompressed size.
+fn decompress_into_with(
+    in_data: &[u8],
+    out_data: &mut [u8],
+    decompress_tile: impl FnMut(&[u8], &mut [u8]) -> Result<usize>,
+) -> Result<usize> {
+    let (tile_stream, tiles) = prepare_decompress(in_data)?;
+    let uncompressed_size = tile_stream.get_uncompressed_size();
+    let out_data = out_data
+        .get_mut(..uncompressed_size)
+        .ok_or(DecompressionError::InsufficientSpace)?;
+    decompress_tiles(&tile_stream, tiles, out_data, decompress_tile)?;
+    Ok(uncompressed_size)
+}
+
+/// Decompress split tiles into `out_data`, which holds exactly the
+/// uncompressed size.
+fn decompress_tiles(
+    tile_stream: &TileStream,
+    tiles: Vec<&[u8]>,
+    out_data: &mut [u8],
+    mut decompress_tile: impl FnMut(&[u8], &mut [u8]) -> Result<usize>,
+) -> Result<()> {
+    for (tile_index, (tile, out_tile)) in tiles
+        .into_iter()
+        .zip(out_data.chunks_mut(tile_stream.tile_size()))
+        .enumerate()
+    {
+        let out_nbytes = decompress_tile(tile, out_tile)?;
+        check_tile_size(tile_stream, tile_index, out_nbytes)?;
+    }
+    Ok(())
+}
+
+/// Compress `in_data` tile by tile with `compress_tile`, and assemble the stream.
+fn compress_with(
+    in_data: &[u8],
+    tile_size: TileSize,
+    compress_tile: impl FnMut(&[u8]) -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    check_compress_size(in_data, tile_size)?;
+    let tiles = in_data
+        .chunks(tile_size.bytes())
+        .map(compress_tile)
+        .collect::<Result<Vec<_>>>()?;
+    assemble_tiles(in_data.len(), tile_size, &tiles)
+}
+
+/// Check that `in_data` is not empty and fits in the `u16` tile count.
+fn check_compress_size(in_data: &[u8], tile_size: TileSize) -> Result<()> {
+    if in_data.is_empty() || in_data.len().div_ceil(tile_size.bytes()) > u16::MAX as usize {
+        Err(CompressionError::CompressionFailed)?;
+    }
+    Ok(())
+}
+
+/// Write the stream header, the tile offset table and the compressed tiles.
+///
+/// Fails if the tile count or the tile offsets overflow the header fields.
+fn assemble_tiles(
+    uncompressed_size: usize,
+    tile_size: TileSize,
+    tiles: &[Vec<u8>],
+) -> Result<Vec<u8>> {
+    // 准备输出流
+    let mut tile_ptrs = Vec::with_capacity(tiles.len());
+    let mut data_pos = 0;
+
+    for tile in tiles {
+        tile_ptrs.push(u32::try_from(data_pos).map_err(|_| CompressionError::CompressionFailed)?);
+        data_pos += tile.len();
+    }
+
+    // tile_ptrs[0]用于存储最后一个tile的大小
+    if !tile_ptrs.is_empty() {
+        let last_tile_size = tiles.last().unwrap().len();
+        tile_ptrs[0] = last_tile_size as u32;
+    }
+
+    // 计算未压缩大小
+    let header = TileStream::new(uncompressed_size, tile_size)?;
+
+    // 组装输出数据
+    let mut output = Vec::new();
+
+    // 写入header
+    output.extend_from_slice(&[header.id]);
+    output.extend_from_slice(&[header.magic]);
+    output.extend_from_slice(&header.num_tiles.to_le_bytes());
+
+    let flags = (header.tile_size_idx & 0x3)
+        | ((header.last_tile_size & 0x3FFFF) << 2)
+        | ((header.reserv1 & 0xFFF) << 20);
+    output.extend_from_slice(&flags.to_le_bytes());
+
+    // 写入tile偏移表
+    for ptr in &tile_ptrs {
+        output.extend_from_slice(&ptr.to_le_bytes());
+    }
+
+    // 写入压缩数据
+    for tile in tiles {
+        output.extend_from_slice(tile);
+    }
+
+    Ok(output)
+}
+
+/// GDeflate decompressor of whole buffers.
+pub struct GDfDecompressor(backend::TileDecompressor);
+
+impl GDfDecompressor {
+    pub fn new() -> Result<GDfDecompressor> {
+        Ok(Self(backend::TileDecompressor::new()?))
     }
 
     pub fn decompress(&mut self, in_data: &[u8]) -> Result<Vec<u8>> {
-        let tile_stream = TileStream::from(&mut io::Cursor::new(in_data))?;
-        if !tile_stream.is_valid() {
-            return Err(DecompressionError::BadData)?;
-        }
-        let uncompressed_size = tile_stream.get_uncompressed_size();
-
-        let mut out_data = vec![0u8; uncompressed_size];
-        let mut _bytes_read = 0;
-
-        unsafe {
-            let tile_offsets = in_data.as_ptr().add(size_of::<u64>()) as *const u32;
-
-            let base_in_ptr = tile_offsets.add(tile_stream.num_tiles as usize) as *const u8;
-
-            for tile_index in 0..tile_stream.num_tiles {
-                let tile_offset = if tile_index > 0 {
-                    *tile_offsets.add(tile_index as usize)
-                } else {
-                    0
-                } as usize;
-
-                let data_size = if tile_index < tile_stream.num_tiles - 1 {
-                    *tile_offsets.add(tile_index as usize + 1) - tile_offset as u32
-                } else {
-                    *tile_offsets
-                };
-                let data_ptr = base_in_ptr.add(tile_offset) as *const std::ffi::c_void;
-
-                let mut compressed_page = sys::libdeflate_gdeflate_in_page {
-                    data: data_ptr,
-                    nbytes: data_size as usize,
-                };
-
-                let output_offset = tile_index as usize * KDEFAULT_TILE_SIZE;
-                let out_ptr = out_data.as_mut_ptr().add(output_offset) as *mut std::ffi::c_void;
-                let mut out_nbytes = 0;
-                let decomp_result: sys::libdeflate_result = sys::libdeflate_gdeflate_decompress(
-                    self.0,
-                    &mut compressed_page,
-                    1,
-                    out_ptr,
-                    KDEFAULT_TILE_SIZE,
-                    &mut out_nbytes,
-                )
-                    as sys::libdeflate_result;
-                match decomp_result {
-                    sys::libdeflate_result_LIBDEFLATE_SUCCESS => _bytes_read += out_nbytes,
-                    sys::libdeflate_result_LIBDEFLATE_BAD_DATA => {
-                        return Err(DecompressionError::BadData)?;
-                    }
-                    sys::libdeflate_result_LIBDEFLATE_INSUFFICIENT_SPACE => {
-                        return Err(DecompressionError::InsufficientSpace)?;
-                    }
-                    _ => {
-                        panic!(
-                            "libdeflate_gdeflate_decompress returned an unknown error type: this is an internal bug that **must** be fixed"
-                        );
-                    }
-                }
-            }
+        decompress_with(in_data, |tile, out_tile| {
+            self.0.decompress_tile(tile, out_tile)
+        })
+    }
+
+    /// Decompress into the start of `out_data`, returning the decompressed size.
+    ///
+    /// `out_data` must hold at least [`uncompressed_size`] bytes,
+    /// otherwise [`DecompressionError::InsufficientSpace`] is returned.
+    pub fn decompress_into(&mut self, in_data: &[u8], out_data: &mut [u8]) -> Result<usize> {
+        decompress_into_with(in_data, out_data, |tile, out_tile| {
+            self.0.decompress_tile(tile, out_tile)
+        })
+    }
+
+    /// Decompress with the tiles spread over the rayon thread pool,
+    /// using the cached decompressor of each worker.
+    ///
+    /// Single tile data is decompressed on the calling thread.
+    #[cfg(feature = "rayon")]
+    pub fn par_decompress(&mut self, in_data: &[u8]) -> Result<Vec<u8>> {
+        use rayon::prelude::*;
+
+        let (tile_stream, tiles) = prepare_decompress(in_data)?;
+        if tiles.len() <= 1 {
+            return self.decompress(in_data);
         }
 
+        let mut out_data = vec![0u8; tile_stream.get_uncompressed_size()];
+        tiles
+            .into_par_iter()
+            .zip(out_data.par_chunks_mut(tile_stream.tile_size()))
+            .enumerate()
+            .try_for_each(|(tile_index, (tile, out_tile))| {
+                let out_nbytes = with_decompressor(|decompressor| {
+                    decompressor.0.decompress_tile(tile, out_tile)
+                })?;
+                check_tile_size(&tile_stream, tile_index, out_nbytes)
+            })?;
         Ok(out_data)
     }
 }
 
-impl Drop for GDfDecompressor {
-    fn drop(&mut self) {
-        unsafe {
-            sys::libdeflate_free_gdeflate_decompressor(self.0);
+/// GDeflate compressor of whole buffers.
+pub struct GDfCompressor {
+    compressor: backend::TileCompressor,
+    level: CompressionLevel,
+    tile_size: TileSize,
+}
+
+impl GDfCompressor {
+    /// Compressor of `level` with the default 64 KiB tiles.
+    pub fn new(level: CompressionLevel) -> Result<GDfCompressor> {
+        Ok(Self {
+            compressor: backend::TileCompressor::new(level)?,
+            level,
+            tile_size: TileSize::default(),
+        })
+    }
+
+    pub fn level(&self) -> CompressionLevel {
+        self.level
+    }
+
+    pub fn tile_size(&self) -> TileSize {
+        self.tile_size
+    }
+
+    pub fn set_tile_size(&mut self, tile_size: TileSize) {
+        self.tile_size = tile_size;
+    }
+
+    pub fn compress(&mut self, in_data: &[u8]) -> Result<Vec<u8>> {
+        compress_with(in_data, self.tile_size, |tile| {
+            self.compressor.compress_tile(tile)
+        })
+    }
+
+    /// Compress with the tiles spread over the rayon thread pool,
+    /// using the cached compressor of each worker.
+    ///
+    /// Single tile data is compressed on the calling thread.
+    #[cfg(feature = "rayon")]
+    pub fn par_compress(&mut self, in_data: &[u8]) -> Result<Vec<u8>> {
+        use rayon::prelude::*;
+
+        if in_data.len() <= self.tile_size.bytes() {
+            return self.compress(in_data);
         }
+        check_compress_size(in_data, self.tile_size)?;
+
+        let tiles = in_data
+            .par_chunks(self.tile_size.bytes())
+            .map(|tile| {
+                with_compressor(self.level, |compressor| {
+                    compressor.compressor.compress_tile(tile)
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        assemble_tiles(in_data.len(), self.tile_size, &tiles)
     }
 }
 
-struct CompressionContext {
-    input_ptr: &'static [u8],
-    input_size: usize,
-    tiles: Vec<Vec<u8>>,
-    num_items: u32,
+thread_local! {
+    static DECOMPRESSOR: Cell<Option<GDfDecompressor>> = const { Cell::new(None) };
+    static COMPRESSORS: RefCell<Vec<GDfCompressor>> = const { RefCell::new(Vec::new()) };
 }
 
-pub struct GDfCompressor(*mut sys::libdeflate_gdeflate_compressor);
+/// Run `f` with the decompressor cached on the calling thread,
+/// creating it on first use.
+///
+/// Nested calls get a temporary decompressor instead of the cached one.
+pub fn with_decompressor<T>(f: impl FnOnce(&mut GDfDecompressor) -> Result<T>) -> Result<T> {
+    let mut decompressor = match DECOMPRESSOR.take() {
+        Some(decompressor) => decompressor,
+        None => GDfDecompressor::new()?,
+    };
+    let result = f(&mut decompressor);
+    DECOMPRESSOR.set(Some(decompressor));
+    result
+}
 
-impl GDfCompressor {
-    pub fn new(level: gdeflate::CompressionLevel) -> Result<GDfCompressor> {
-        let compressor = unsafe { sys::libdeflate_alloc_gdeflate_compressor(level as i32) };
-        if compressor.is_null() {
-            Err(CompressionError::CompressorCreationFailed)?
-        } else {
-            Ok(Self(compressor))
+/// Run `f` with a compressor of `level` cached on the calling thread,
+/// creating it on first use. The compressor starts with the default tile size.
+///
+/// Nested calls get a temporary compressor instead of the cached one.
+pub fn with_compressor<T>(
+    level: CompressionLevel,
+    f: impl FnOnce(&mut GDfCompressor) -> Result<T>,
+) -> Result<T> {
+    let cached = COMPRESSORS.with_borrow_mut(|compressors| {
+        let idx = compressors
+            .iter()
+            .position(|compressor| compressor.level == level)?;
+        Some(compressors.swap_remove(idx))
+    });
+    let mut compressor = match cached {
+        Some(compressor) => compressor,
+        None => GDfCompressor::new(level)?,
+    };
+    compressor.set_tile_size(TileSize::default());
+    let result = f(&mut compressor);
+    COMPRESSORS.with_borrow_mut(|compressors| compressors.push(compressor));
+    result
+}
+
+/// Streaming GDeflate decompressor over an [`io::Read`].
+///
+/// The header and tile table are read upfront, then tiles are
+/// decompressed one at a time as the output is read.
+pub struct GDfReader<R: io::Read> {
+    inner: R,
+    decompressor: backend::TileDecompressor,
+    tile_stream: TileStream,
+    tile_ranges: Vec<Range<usize>>,
+    /// Position in `inner`, relative to the end of the tile table.
+    compressed_pos: usize,
+    next_tile: usize,
+    compressed_tile: Vec<u8>,
+    tile: Vec<u8>,
+    tile_pos: usize,
+}
+
+impl<R: io::Read> GDfReader<R> {
+    pub fn new(mut inner: R) -> Result<Self> {
+        let tile_stream = TileStream::from(&mut inner)?;
+        if !tile_stream.is_valid() {
+            Err(DecompressionError::BadData)?;
         }
+        let tile_table = read_tile_table(&tile_stream, &mut inner)?;
+        let tile_ranges = tile_ranges(&tile_table)?;
+        Ok(Self {
+            inner,
+            decompressor: backend::TileDecompressor::new()?,
+            tile_stream,
+            tile_ranges,
+            compressed_pos: 0,
+            next_tile: 0,
+            compressed_tile: Vec::new(),
+            tile: Vec::new(),
+            tile_pos: 0,
+        })
     }
 
-    fn compress_tile(&mut self, input: &[u8]) -> Result<Vec<u8>> {
-        let mut page_count = 0;
-        let scratch_size = unsafe {
-            sys::libdeflate_gdeflate_compress_bound(self.0, input.len(), &mut page_count)
-        };
-        assert_eq!(page_count, 1);
+    /// Total size of the decompressed data.
+    pub fn uncompressed_size(&self) -> usize {
+        self.tile_stream.get_uncompressed_size()
+    }
 
-        let mut scratch_buffer = vec![0u8; scratch_size];
-        let mut compressed_page = sys::libdeflate_gdeflate_out_page {
-            data: scratch_buffer.as_mut_ptr() as *mut std::ffi::c_void,
-            nbytes: scratch_size,
-        };
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
 
-        let result = unsafe {
-            sys::libdeflate_gdeflate_compress(
-                self.0,
-                input.as_ptr() as *const std::ffi::c_void,
-                input.len(),
-                &mut compressed_page,
-                1,
-            )
-        };
+    /// Read and decompress the next tile into `self.tile`.
+    fn next_tile(&mut self) -> Result<()> {
+        let tile_index = self.next_tile;
+        let range = self.tile_ranges[tile_index].clone();
+        // tiles can only be streamed in file order
+        let gap = range
+            .start
+            .checked_sub(self.compressed_pos)
+            .ok_or(DecompressionError::BadData)?;
+        io::copy(&mut (&mut self.inner).take(gap as u64), &mut io::sink())?;
+        // the range comes from the tile table, check it before allocating
+        if range.len() > max_compressed_tile_size(self.tile_stream.tile_size()) {
+            Err(DecompressionError::BadData)?;
+        }
+        self.compressed_tile.resize(range.len(), 0);
+        self.inner.read_exact(&mut self.compressed_tile)?;
+        self.compressed_pos = range.end;
 
-        if result == 0 {
-            Err(CompressionError::CompressionFailed)?
+        self.tile
+            .resize(self.tile_stream.tile_uncompressed_size(tile_index), 0);
+        let out_nbytes = self
+            .decompressor
+            .decompress_tile(&self.compressed_tile, &mut self.tile)?;
+        check_tile_size(&self.tile_stream, tile_index, out_nbytes)?;
+        self.tile_pos = 0;
+        self.next_tile += 1;
+        Ok(())
+    }
+}
+
+impl<R: io::Read> io::Read for GDfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.tile_pos == self.tile.len() {
+            if self.next_tile == self.tile_ranges.len() {
+                return Ok(0);
+            }
+            self.next_tile()?;
         }
+        let len = buf.len().min(self.tile.len() - self.tile_pos);
+        buf[..len].copy_from_slice(&self.tile[self.tile_pos..self.tile_pos + len]);
+        self.tile_pos += len;
+        Ok(len)
+    }
+}
 
-        let compressed_data = unsafe {
-            std::slice::from_raw_parts(compressed_page.data as *const u8, compressed_page.nbytes)
-                .to_vec()
-        };
+/// Streaming GDeflate compressor over an [`io::Write`].
+///
+/// Tiles are compressed as soon as they are filled, but the tile table
+/// precedes them in the stream, so nothing reaches `inner` before
+/// [`GDfWriter::finish`]. Dropping the writer without finishing loses the data.
+pub struct GDfWriter<W: io::Write> {
+    inner: W,
+    compressor: GDfCompressor,
+    tile: Vec<u8>,
+    compressed_tiles: Vec<Vec<u8>>,
+    uncompressed_size: usize,
+}
 
-        Ok(compressed_data)
+impl<W: io::Write> GDfWriter<W> {
+    /// Writer of `level` with the default 64 KiB tiles.
+    pub fn new(inner: W, level: CompressionLevel) -> Result<Self> {
+        Ok(Self::from_compressor(inner, GDfCompressor::new(level)?))
     }
 
-    pub fn compress(&mut self, in_data: &[u8]) -> Result<Vec<u8>> {
-        if in_data.is_empty() {
-            return Err(CompressionError::CompressionFailed)?;
+    /// Writer using the level and tile size of `compressor`.
+    pub fn from_compressor(inner: W, compressor: GDfCompressor) -> Self {
+        Self {
+            inner,
+            tile: Vec::with_capacity(compressor.tile_size.bytes()),
+            compressor,
+            compressed_tiles: Vec::new(),
+            uncompressed_size: 0,
         }
+    }
+
+    fn tile_size(&self) -> usize {
+        self.compressor.tile_size.bytes()
+    }
 
-        let num_items = in_data.len().div_ceil(KDEFAULT_TILE_SIZE) as u32;
-        let mut tiles = Vec::with_capacity(num_items as usize);
+    /// Compress the remaining data and write the whole stream to `inner`.
+    pub fn finish(mut self) -> Result<W> {
+        if !self.tile.is_empty() {
+            self.compress_tile()?;
+        }
+        self.inner.write_all(&assemble_tiles(
+            self.uncompressed_size,
+            self.compressor.tile_size,
+            &self.compressed_tiles,
+        )?)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
 
-        // 压缩每个tile
-        for tile_index in 0..num_items {
-            let tile_pos = tile_index as usize * KDEFAULT_TILE_SIZE;
-            let remaining = in_data.len() - tile_pos;
-            let uncompressed_size = std::cmp::min(remaining, KDEFAULT_TILE_SIZE);
+    fn compress_tile(&mut self) -> Result<()> {
+        let compressed_tile = self.compressor.compressor.compress_tile(&self.tile)?;
+        self.compressed_tiles.push(compressed_tile);
+        self.tile.clear();
+        Ok(())
+    }
+}
 
-            let tile_data = &in_data[tile_pos..tile_pos + uncompressed_size];
-            let compressed_tile = self.compress_tile(tile_data)?;
-            tiles.push(compressed_tile);
+impl<W: io::Write> io::Write for GDfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let tile_size = self.tile_size();
+        if self.tile.len() == tile_size {
+            self.compress_tile()?;
+        }
+        let len = buf.len().min(tile_size - self.tile.len());
+        // limited by the u16 tile count
+        if self.uncompressed_size + len > u16::MAX as usize * tile_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "GDeflate stream exceeds the maximum tile count",
+            ));
         }
+        self.tile.extend_from_slice(&buf[..len]);
+        self.uncompressed_size += len;
+        Ok(len)
+    }
 
-        // 准备输出流
-        let mut tile_ptrs = Vec::with_capacity(num_items as usize);
-        let mut data_pos = 0;
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
 
-        for tile in &tiles {
-            tile_ptrs.push(data_pos as u32);
-            data_pos += tile.len();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_data(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i % 251) as u8 ^ (i / 4096) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_gdf_rw() {
+        let data = sample_data(3 * KDEFAULT_TILE_SIZE + 1234);
+        let mut compressor = GDfCompressor::new(CompressionLevel::Level6).unwrap();
+        let compressed = compressor.compress(&data).unwrap();
+        assert_eq!(uncompressed_size(&compressed).unwrap(), data.len());
+
+        let mut decompressor = GDfDecompressor::new().unwrap();
+        assert_eq!(decompressor.decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_gdf_max_size() {
+        // the u16 tile count limits streams to just under 4 GiB
+        let max_size = u16::MAX as usize * KDEFAULT_TILE_SIZE;
+        let header = TileStream::new(max_size, TileSize::KiB64).unwrap();
+        assert_eq!(header.num_tiles, u16::MAX);
+        assert_eq!(header.get_uncompressed_size(), max_size);
+        let header = TileStream::new(max_size - 1, TileSize::KiB64).unwrap();
+        assert_eq!(header.get_uncompressed_size(), max_size - 1);
+        assert!(matches!(
+            TileStream::new(max_size + 1, TileSize::KiB64),
+            Err(Error::Compression(CompressionError::CompressionFailed))
+        ));
+        assert!(assemble_tiles(max_size + 1, TileSize::KiB64, &[]).is_err());
+    }
+
+    #[test]
+    fn test_gdf_tile_size() {
+        let data = sample_data(3 * KDEFAULT_TILE_SIZE + 1234);
+        let mut compressor = GDfCompressor::new(CompressionLevel::Level6).unwrap();
+        compressor.set_tile_size(TileSize::KiB64);
+        let compressed = compressor.compress(&data).unwrap();
+        assert_eq!(u16::from_le_bytes([compressed[2], compressed[3]]), 4);
+        assert_eq!(compressed[4] & 0x3, TileSize::KiB64.index() as u8);
+
+        // the tile size reaches the streaming writer
+        let mut writer = GDfWriter::from_compressor(Vec::new(), compressor);
+        io::Write::write_all(&mut writer, &data).unwrap();
+        assert_eq!(writer.finish().unwrap(), compressed);
+
+        // only index 1 is defined by the stream format
+        let mut decompressor = GDfDecompressor::new().unwrap();
+        for index in [0, 2, 3] {
+            let mut bad = compressed.clone();
+            bad[4] = (bad[4] & !0x3) | index;
+            assert!(uncompressed_size(&bad).is_err());
+            assert!(decompressor.decompress(&bad).is_err());
+            assert!(GDfReader::new(bad.as_slice()).is_err());
         }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_gdf_par_rw() {
+        let data = sample_data(5 * KDEFAULT_TILE_SIZE + 1234);
+        let mut compressor = GDfCompressor::new(CompressionLevel::Level6).unwrap();
+        let compressed = compressor.par_compress(&data).unwrap();
+        assert_eq!(compressed, compressor.compress(&data).unwrap());
+        let mut decompressor = GDfDecompressor::new().unwrap();
+        assert_eq!(decompressor.par_decompress(&compressed).unwrap(), data);
 
-        // tile_ptrs[0]用于存储最后一个tile的大小
-        if !tile_ptrs.is_empty() {
-            let last_tile_size = tiles.last().unwrap().len();
-            tile_ptrs[0] = last_tile_size as u32;
+        assert!(compressor.par_compress(&[]).is_err());
+        let mut bad = compressed.clone();
+        bad[8 + 4..][..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decompressor.par_decompress(&bad).is_err());
+        let truncated = &compressed[..compressed.len() - 1];
+        assert!(decompressor.par_decompress(truncated).is_err());
+    }
+
+    #[test]
+    fn test_gdf_truncated() {
+        let data = sample_data(2 * KDEFAULT_TILE_SIZE + 100);
+        let mut compressor = GDfCompressor::new(CompressionLevel::Level6).unwrap();
+        let compressed = compressor.compress(&data).unwrap();
+
+        let mut decompressor = GDfDecompressor::new().unwrap();
+        for len in (0..compressed.len()).step_by(7) {
+            assert!(decompressor.decompress(&compressed[..len]).is_err());
         }
+    }
 
-        // 计算未压缩大小
-        let header = TileStream::new(in_data.len());
+    #[test]
+    fn test_gdf_corrupted_header() {
+        let data = sample_data(2 * KDEFAULT_TILE_SIZE + 100);
+        let mut compressor = GDfCompressor::new(CompressionLevel::Level6).unwrap();
+        let compressed = compressor.compress(&data).unwrap();
+        let mut decompressor = GDfDecompressor::new().unwrap();
 
-        // 组装输出数据
-        let mut output = Vec::new();
+        // more tiles than the offset table holds
+        let mut bad = compressed.clone();
+        bad[2..4].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(decompressor.decompress(&bad).is_err());
 
-        // 写入header
-        output.extend_from_slice(&[header.id]);
-        output.extend_from_slice(&[header.magic]);
-        output.extend_from_slice(&header.num_tiles.to_le_bytes());
+        // a header claiming ~4 GiB with no tile data is rejected before allocating
+        let mut bad = compressed[..HEADER_SIZE].to_vec();
+        bad[2..4].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(uncompressed_size(&bad).unwrap() > u32::MAX as usize / 2);
+        assert!(matches!(
+            decompressor.decompress(&bad),
+            Err(Error::Decompression(DecompressionError::BadData))
+        ));
 
-        let flags = (header.tile_size_idx & 0x3)
-            | ((header.last_tile_size & 0x3FFFF) << 2)
-            | ((header.reserv1 & 0xFFF) << 20);
-        output.extend_from_slice(&flags.to_le_bytes());
+        // no tiles but a last tile size
+        let mut bad = compressed.clone();
+        bad[2..4].copy_from_slice(&0u16.to_le_bytes());
+        assert!(uncompressed_size(&bad).is_err());
+        assert!(decompressor.decompress(&bad).is_err());
 
-        // 写入tile偏移表
-        for ptr in &tile_ptrs {
-            output.extend_from_slice(&ptr.to_le_bytes());
+        // tile offsets out of bounds or out of order
+        for (entry, value) in [(0, u32::MAX), (1, u32::MAX), (2, 0), (1, 1 << 20)] {
+            let mut bad = compressed.clone();
+            bad[8 + entry * 4..][..4].copy_from_slice(&value.to_le_bytes());
+            assert!(decompressor.decompress(&bad).is_err());
         }
 
-        // 写入压缩数据
-        for (i, tile) in tiles.iter().enumerate() {
-            let tile_offset = if i == 0 { 0 } else { tile_ptrs[i] as usize };
-            while output.len() < tile_offset {
-                output.push(0);
+        // last tile larger than the output
+        let mut bad = compressed.clone();
+        bad[4..8].copy_from_slice(&(1u32 | (50 << 2)).to_le_bytes());
+        assert!(decompressor.decompress(&bad).is_err());
+    }
+
+    #[test]
+    fn test_gdf_decompress_into() {
+        let data = sample_data(2 * KDEFAULT_TILE_SIZE + 100);
+        let mut compressor = GDfCompressor::new(CompressionLevel::Level6).unwrap();
+        let compressed = compressor.compress(&data).unwrap();
+        let mut decompressor = GDfDecompressor::new().unwrap();
+
+        let mut out_data = vec![0xAA; data.len() + 10];
+        let out_nbytes = decompressor
+            .decompress_into(&compressed, &mut out_data)
+            .unwrap();
+        assert_eq!(out_nbytes, data.len());
+        assert_eq!(&out_data[..out_nbytes], data);
+        assert!(out_data[out_nbytes..].iter().all(|&b| b == 0xAA));
+
+        let result = decompressor.decompress_into(&compressed, &mut out_data[..data.len() - 1]);
+        assert!(matches!(
+            result,
+            Err(Error::Decompression(DecompressionError::InsufficientSpace))
+        ));
+    }
+
+    #[test]
+    fn test_gdf_reader_writer() {
+        use std::io::{Read, Write};
+
+        let data = sample_data(3 * KDEFAULT_TILE_SIZE + 1234);
+        let mut writer = GDfWriter::new(Vec::new(), CompressionLevel::Level6).unwrap();
+        for chunk in data.chunks(10_000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let compressed = writer.finish().unwrap();
+        let mut compressor = GDfCompressor::new(CompressionLevel::Level6).unwrap();
+        assert_eq!(compressed, compressor.compress(&data).unwrap());
+
+        let mut reader = GDfReader::new(compressed.as_slice()).unwrap();
+        assert_eq!(reader.uncompressed_size(), data.len());
+        let mut out_data = Vec::new();
+        let mut buf = [0u8; 1000];
+        loop {
+            let len = reader.read(&mut buf).unwrap();
+            if len == 0 {
+                break;
             }
-            output.extend_from_slice(tile);
+            out_data.extend_from_slice(&buf[..len]);
         }
+        assert_eq!(out_data, data);
+
+        let truncated = &compressed[..compressed.len() - 1];
+        let mut reader = GDfReader::new(truncated).unwrap();
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        assert!(GDfReader::new(&compressed[..10]).is_err());
+
+        // a tile larger than any compressed tile
+        let mut bad = compressor.compress(&data[..1000]).unwrap();
+        bad[HEADER_SIZE..][..4].copy_from_slice(&(1u32 << 30).to_le_bytes());
+        let mut reader = GDfReader::new(bad.as_slice()).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
 
-        Ok(output)
+        // empty input gives a header only stream
+        let writer = GDfWriter::new(Vec::new(), CompressionLevel::Level6).unwrap();
+        let compressed = writer.finish().unwrap();
+        assert_eq!(compressed.len(), HEADER_SIZE);
+        let mut reader = GDfReader::new(compressed.as_slice()).unwrap();
+        assert_eq!(reader.uncompressed_size(), 0);
+        assert_eq!(reader.read_to_end(&mut Vec::new()).unwrap(), 0);
     }
-}
 
-impl Drop for GDfCompressor {
-    fn drop(&mut self) {
-        unsafe {
-            sys::libdeflate_free_gdeflate_compressor(self.0);
+    #[test]
+    fn test_gdf_cached_codecs() {
+        let data = sample_data(2 * KDEFAULT_TILE_SIZE + 100);
+        let compressed = with_compressor(CompressionLevel::Level6, |compressor| {
+            compressor.compress(&data)
+        })
+        .unwrap();
+        let cached = with_compressor(CompressionLevel::Level6, |compressor| {
+            assert_eq!(compressor.level(), CompressionLevel::Level6);
+            compressor.compress(&data)
+        })
+        .unwrap();
+        assert_eq!(cached, compressed);
+
+        // nested calls get their own decompressor
+        let out_data = with_decompressor(|decompressor| {
+            let nested = with_decompressor(|nested| nested.decompress(&compressed))?;
+            assert_eq!(nested, decompressor.decompress(&compressed)?);
+            Ok(nested)
+        })
+        .unwrap();
+        assert_eq!(out_data, data);
+
+        // codecs can move to other threads
+        let mut decompressor = GDfDecompressor::new().unwrap();
+        let handle = std::thread::spawn(move || decompressor.decompress(&compressed).unwrap());
+        assert_eq!(handle.join().unwrap(), data);
+    }
+
+    /// Compressed mips of the test files.
+    #[cfg(feature = "libdeflate")]
+    fn test_file_streams() -> Vec<Vec<u8>> {
+        [
+            "test_files/ch04_000_0000_1001_ALBD.tex.241106027",
+            "test_files/ch04_000_0000_1002_MB.tex.241106027",
+        ]
+        .into_iter()
+        .flat_map(|path| {
+            let data = std::fs::read(path).unwrap();
+            let tex = crate::tex::Tex::from_reader(&mut io::Cursor::new(data)).unwrap();
+            tex.mip_datas
+                .into_iter()
+                .filter(|mip| mip.is_compressed())
+                .map(|mip| mip.texture_data)
+        })
+        .collect()
+    }
+
+    #[cfg(feature = "libdeflate")]
+    #[test]
+    fn test_gdf_pure_decompress_matches_libdeflate() {
+        let streams = test_file_streams();
+        assert!(!streams.is_empty());
+
+        let mut c_decompressor = libdeflate::TileDecompressor::new().unwrap();
+        let mut rust_decompressor = pure::TileDecompressor::new().unwrap();
+        for stream in &streams {
+            let expected = decompress_with(stream, |tile, out| {
+                c_decompressor.decompress_tile(tile, out)
+            })
+            .unwrap();
+            let actual = decompress_with(stream, |tile, out| {
+                rust_decompressor.decompress_tile(tile, out)
+            })
+            .unwrap();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[cfg(feature = "libdeflate")]
+    #[test]
+    fn test_gdf_pure_compress_interop() {
+        let mut c_decompressor = libdeflate::TileDecompressor::new().unwrap();
+        let mut rust_decompressor = pure::TileDecompressor::new().unwrap();
+
+        let mut inputs = vec![sample_data(2 * KDEFAULT_TILE_SIZE + 100)];
+        inputs.extend(test_file_streams().iter().take(3).map(|stream| {
+            decompress_with(stream, |tile, out| {
+                c_decompressor.decompress_tile(tile, out)
+            })
+            .unwrap()
+        }));
+
+        for level in [
+            CompressionLevel::None,
+            CompressionLevel::Level4,
+            CompressionLevel::Level12,
+        ] {
+            let mut c_compressor = libdeflate::TileCompressor::new(level).unwrap();
+            let mut rust_compressor = pure::TileCompressor::new(level).unwrap();
+            for input in &inputs {
+                // each implementation decodes the other's output
+                let compressed = compress_with(input, TileSize::default(), |tile| {
+                    rust_compressor.compress_tile(tile)
+                })
+                .unwrap();
+                let decompressed = decompress_with(&compressed, |tile, out| {
+                    c_decompressor.decompress_tile(tile, out)
+                })
+                .unwrap();
+                assert_eq!(&decompressed, input, "{level:?}");
+
+                let compressed = compress_with(input, TileSize::default(), |tile| {
+                    c_compressor.compress_tile(tile)
+                })
+                .unwrap();
+                let decompressed = decompress_with(&compressed, |tile, out| {
+                    rust_decompressor.decompress_tile(tile, out)
+                })
+                .unwrap();
+                assert_eq!(&decompressed, input, "{level:?}");
+            }
         }
     }
 }
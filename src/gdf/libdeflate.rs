@@ -0,0 +1,122 @@
+//! libdeflate GDeflate tile codec, through `gdeflate::sys`.
+
+use gdeflate::sys;
+
+use super::{CompressionError, CompressionLevel, DecompressionError, Result};
+
+pub(super) struct TileDecompressor(*mut sys::libdeflate_gdeflate_decompressor);
+
+// SAFETY: a libdeflate decompressor only holds its own scratch state and has
+// no thread affinity. It is never shared, as every use goes through `&mut self`.
+unsafe impl Send for TileDecompressor {}
+
+impl TileDecompressor {
+    pub fn new() -> Result<Self> {
+        let decompressor = unsafe { sys::libdeflate_alloc_gdeflate_decompressor() };
+        if decompressor.is_null() {
+            Err(DecompressionError::DecompressorCreationFailed)?
+        } else {
+            Ok(Self(decompressor))
+        }
+    }
+
+    /// Decompress a single tile into `out_data`, returning the decompressed size.
+    pub fn decompress_tile(&mut self, tile: &[u8], out_data: &mut [u8]) -> Result<usize> {
+        let mut compressed_page = sys::libdeflate_gdeflate_in_page {
+            data: tile.as_ptr() as *const std::ffi::c_void,
+            nbytes: tile.len(),
+        };
+        let mut out_nbytes = 0;
+        // SAFETY: the input page and output buffer are valid slices,
+        // and libdeflate never writes more than `out_data.len()` bytes.
+        let result = unsafe {
+            sys::libdeflate_gdeflate_decompress(
+                self.0,
+                &mut compressed_page,
+                1,
+                out_data.as_mut_ptr() as *mut std::ffi::c_void,
+                out_data.len(),
+                &mut out_nbytes,
+            )
+        } as sys::libdeflate_result;
+
+        match result {
+            sys::libdeflate_result_LIBDEFLATE_SUCCESS => Ok(out_nbytes),
+            sys::libdeflate_result_LIBDEFLATE_BAD_DATA => Err(DecompressionError::BadData)?,
+            sys::libdeflate_result_LIBDEFLATE_INSUFFICIENT_SPACE => {
+                Err(DecompressionError::InsufficientSpace)?
+            }
+            _ => Err(DecompressionError::DecompressionFailed)?,
+        }
+    }
+}
+
+impl Drop for TileDecompressor {
+    fn drop(&mut self) {
+        unsafe {
+            sys::libdeflate_free_gdeflate_decompressor(self.0);
+        }
+    }
+}
+
+pub(super) struct TileCompressor(*mut sys::libdeflate_gdeflate_compressor);
+
+// SAFETY: same as `TileDecompressor`, the compressor state is owned and
+// only used through `&mut self`.
+unsafe impl Send for TileCompressor {}
+
+impl TileCompressor {
+    pub fn new(level: CompressionLevel) -> Result<Self> {
+        let compressor = unsafe { sys::libdeflate_alloc_gdeflate_compressor(level as i32) };
+        if compressor.is_null() {
+            Err(CompressionError::CompressorCreationFailed)?
+        } else {
+            Ok(Self(compressor))
+        }
+    }
+
+    pub fn compress_tile(&mut self, input: &[u8]) -> Result<Vec<u8>> {
+        let mut page_count = 0;
+        let scratch_size = unsafe {
+            sys::libdeflate_gdeflate_compress_bound(self.0, input.len(), &mut page_count)
+        };
+        if page_count != 1 {
+            Err(CompressionError::CompressionFailed)?
+        }
+
+        let mut scratch_buffer = vec![0u8; scratch_size];
+        let mut compressed_page = sys::libdeflate_gdeflate_out_page {
+            data: scratch_buffer.as_mut_ptr() as *mut std::ffi::c_void,
+            nbytes: scratch_size,
+        };
+
+        let result = unsafe {
+            sys::libdeflate_gdeflate_compress(
+                self.0,
+                input.as_ptr() as *const std::ffi::c_void,
+                input.len(),
+                &mut compressed_page,
+                1,
+            )
+        };
+
+        if result == 0 {
+            Err(CompressionError::CompressionFailed)?
+        }
+
+        let compressed_data = unsafe {
+            std::slice::from_raw_parts(compressed_page.data as *const u8, compressed_page.nbytes)
+                .to_vec()
+        };
+
+        Ok(compressed_data)
+    }
+}
+
+impl Drop for TileCompressor {
+    fn drop(&mut self) {
+        unsafe {
+            sys::libdeflate_free_gdeflate_compressor(self.0);
+        }
+    }
+}
@@ -0,0 +1,1110 @@
+//! Pure-Rust GDeflate tile codec.
+//!
+//! A GDeflate tile is a Deflate64 stream whose bits are spread over 32
+//! interleaved bit streams, refilled with 32-bit packets from a shared input,
+//! with each match offset deferred to the next visit of its stream.
+//! The bit layout follows the libdeflate implementation exactly,
+//! so tiles are interchangeable with the C backend.
+//!
+//! Reference: `gdeflate_decompress_template.h` and `deflate_compress.c`
+//! of the libdeflate fork vendored by `gdeflate-sys`.
+
+use super::{CompressionError, CompressionLevel, DecompressionError, KDEFAULT_TILE_SIZE, Result};
+
+const NUM_STREAMS: usize = 32;
+const BITS_PER_PACKET: u32 = 32;
+const LOW_WATERMARK_BITS: u32 = 32;
+
+const BLOCKTYPE_UNCOMPRESSED: u32 = 0;
+const BLOCKTYPE_STATIC_HUFFMAN: u32 = 1;
+const BLOCKTYPE_DYNAMIC_HUFFMAN: u32 = 2;
+
+const NUM_LITLEN_SYMS: usize = 288;
+const NUM_OFFSET_SYMS: usize = 32;
+const NUM_PRECODE_SYMS: usize = 19;
+const END_OF_BLOCK: usize = 256;
+
+const MAX_CODEWORD_LEN: u32 = 15;
+const MAX_PRE_CODEWORD_LEN: u32 = 7;
+const MIN_MATCH_LEN: usize = 3;
+/// Longest match the encoder emits. Deflate64 allows longer ones
+/// through length slot 28, which is only handled when decoding.
+const MAX_MATCH_LEN: usize = 258;
+const MAX_UNCOMPRESSED_BLOCK_LEN: usize = u16::MAX as usize;
+
+const PRECODE_LENS_PERMUTATION: [usize; NUM_PRECODE_SYMS] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Deflate64 length slot base values.
+const LENGTH_BASE: [u32; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 3,
+];
+const LENGTH_EXTRA_BITS: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 16,
+];
+
+/// Deflate64 offset slot base values.
+const OFFSET_BASE: [u32; NUM_OFFSET_SYMS] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577, 32769, 49153,
+];
+const OFFSET_EXTRA_BITS: [u32; NUM_OFFSET_SYMS] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13, 14, 14,
+];
+
+/// Codeword lengths of the static Huffman codes.
+fn static_lens() -> ([u8; NUM_LITLEN_SYMS], [u8; NUM_OFFSET_SYMS]) {
+    let mut litlen_lens = [0u8; NUM_LITLEN_SYMS];
+    litlen_lens[..144].fill(8);
+    litlen_lens[144..256].fill(9);
+    litlen_lens[256..280].fill(7);
+    litlen_lens[280..].fill(8);
+    (litlen_lens, [5; NUM_OFFSET_SYMS])
+}
+
+/// Canonical Huffman codewords, bit-reversed for LSB-first output.
+fn canonical_codewords(lens: &[u8]) -> Vec<u16> {
+    let mut len_counts = [0u16; MAX_CODEWORD_LEN as usize + 1];
+    for &len in lens {
+        len_counts[len as usize] += 1;
+    }
+    len_counts[0] = 0;
+
+    let mut next_code = [0u16; MAX_CODEWORD_LEN as usize + 2];
+    for len in 1..=MAX_CODEWORD_LEN as usize {
+        next_code[len + 1] = (next_code[len] + len_counts[len]) << 1;
+    }
+
+    lens.iter()
+        .map(|&len| {
+            if len == 0 {
+                return 0;
+            }
+            let code = next_code[len as usize];
+            next_code[len as usize] += 1;
+            code.reverse_bits() >> (16 - len)
+        })
+        .collect()
+}
+
+/// Huffman decode table, indexed by the next `table_bits` input bits.
+///
+/// Entries hold `symbol << 4 | codeword length`; a length of 0 marks
+/// bit patterns that no codeword uses.
+struct DecodeTable {
+    entries: Vec<u16>,
+    table_bits: u32,
+}
+
+impl DecodeTable {
+    fn new(lens: &[u8]) -> Result<Self> {
+        // over-subscribed codes are invalid, incomplete ones only fail when
+        // an unused bit pattern is actually decoded
+        let mut codespace_left = 1i32;
+        for len in 1..=MAX_CODEWORD_LEN as u8 {
+            codespace_left <<= 1;
+            codespace_left -= lens.iter().filter(|&&l| l == len).count() as i32;
+            if codespace_left < 0 {
+                return Err(DecompressionError::BadData)?;
+            }
+        }
+
+        let table_bits = lens.iter().copied().max().unwrap_or(0).max(1) as u32;
+        let mut entries = vec![0u16; 1 << table_bits];
+        for (symbol, (&len, codeword)) in lens.iter().zip(canonical_codewords(lens)).enumerate() {
+            if len == 0 {
+                continue;
+            }
+            let entry = (symbol as u16) << 4 | len as u16;
+            for index in (codeword as usize..entries.len()).step_by(1 << len) {
+                entries[index] = entry;
+            }
+        }
+        Ok(Self {
+            entries,
+            table_bits,
+        })
+    }
+}
+
+/// Reader side of the 32 interleaved bit streams.
+struct InputStreams<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// Packets read past the end of the input, which read as zeros.
+    overrun: usize,
+    bitbuf: [u64; NUM_STREAMS],
+    bitsleft: [u32; NUM_STREAMS],
+    idx: usize,
+}
+
+impl<'a> InputStreams<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        let mut streams = Self {
+            input,
+            pos: 0,
+            overrun: 0,
+            bitbuf: [0; NUM_STREAMS],
+            bitsleft: [0; NUM_STREAMS],
+            idx: 0,
+        };
+        // every stream starts with one packet
+        for _ in 0..NUM_STREAMS {
+            streams.advance();
+        }
+        streams
+    }
+
+    /// Refill the current stream with a packet if it runs low.
+    fn ensure_bits(&mut self) {
+        if self.bitsleft[self.idx] >= LOW_WATERMARK_BITS {
+            return;
+        }
+        let packet = match self.input.get(self.pos..self.pos + 4) {
+            Some(packet) => u32::from_le_bytes(packet.try_into().unwrap()),
+            None => {
+                self.overrun += 1;
+                0
+            }
+        };
+        self.pos += BITS_PER_PACKET as usize / 8;
+        self.bitbuf[self.idx] |= (packet as u64) << self.bitsleft[self.idx];
+        self.bitsleft[self.idx] += BITS_PER_PACKET;
+    }
+
+    fn reset(&mut self) {
+        self.idx = 0;
+    }
+
+    /// Refill the current stream and move to the next one.
+    fn advance(&mut self) {
+        self.ensure_bits();
+        self.idx = (self.idx + 1) % NUM_STREAMS;
+    }
+
+    fn pop_bits(&mut self, n: u32) -> u32 {
+        let bits = (self.bitbuf[self.idx] & ((1 << n) - 1)) as u32;
+        self.bitbuf[self.idx] >>= n;
+        self.bitsleft[self.idx] -= n;
+        bits
+    }
+
+    fn decode(&mut self, table: &DecodeTable) -> Result<usize> {
+        let index = self.bitbuf[self.idx] & ((1 << table.table_bits) - 1);
+        let entry = table.entries[index as usize];
+        let len = (entry & 0xF) as u32;
+        if len == 0 {
+            return Err(DecompressionError::BadData)?;
+        }
+        self.pop_bits(len);
+        Ok((entry >> 4) as usize)
+    }
+
+    /// Bytes left in the input, counting the buffered bits.
+    fn available_bytes(&self) -> usize {
+        let buffered_bits: u32 = self.bitsleft.iter().sum();
+        self.input.len().saturating_sub(self.pos) + buffered_bits.div_ceil(8) as usize
+    }
+}
+
+/// A match whose offset is read on the next visit of its stream.
+#[derive(Debug, Clone, Copy)]
+struct DeferredCopy {
+    out_pos: usize,
+    length: usize,
+}
+
+/// Stateless GDeflate tile decompressor.
+pub(super) struct TileDecompressor;
+
+impl TileDecompressor {
+    pub fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    /// Decompress a single tile into `out_data`, returning the decompressed size.
+    pub fn decompress_tile(&mut self, tile: &[u8], out_data: &mut [u8]) -> Result<usize> {
+        let mut streams = InputStreams::new(tile);
+        let mut copies = [None::<DeferredCopy>; NUM_STREAMS];
+        let mut out_pos = 0;
+
+        loop {
+            // a stream must not need more than its last unused packet
+            // past the end of the input
+            if streams.overrun > NUM_STREAMS {
+                return Err(DecompressionError::BadData)?;
+            }
+
+            streams.reset();
+            let is_final_block = streams.pop_bits(1) != 0;
+            let block_type = streams.pop_bits(2);
+            streams.ensure_bits();
+
+            let (litlen_table, offset_table) = match block_type {
+                BLOCKTYPE_UNCOMPRESSED => {
+                    let len = streams.pop_bits(16) as usize;
+                    if len > out_data.len() - out_pos {
+                        return Err(DecompressionError::InsufficientSpace)?;
+                    }
+                    if len > streams.available_bytes() {
+                        return Err(DecompressionError::BadData)?;
+                    }
+                    for byte in &mut out_data[out_pos..out_pos + len] {
+                        *byte = streams.pop_bits(8) as u8;
+                        streams.advance();
+                    }
+                    out_pos += len;
+                    for _ in 0..NUM_STREAMS {
+                        streams.advance();
+                    }
+                    if is_final_block {
+                        break;
+                    }
+                    continue;
+                }
+                BLOCKTYPE_STATIC_HUFFMAN => {
+                    let (litlen_lens, offset_lens) = static_lens();
+                    (
+                        DecodeTable::new(&litlen_lens)?,
+                        DecodeTable::new(&offset_lens)?,
+                    )
+                }
+                BLOCKTYPE_DYNAMIC_HUFFMAN => read_dynamic_tables(&mut streams)?,
+                _ => return Err(DecompressionError::BadData)?,
+            };
+
+            streams.reset();
+            loop {
+                if let Some(copy) = copies[streams.idx].take() {
+                    do_copy(&mut streams, &offset_table, out_data, copy)?;
+                    streams.advance();
+                    continue;
+                }
+
+                let symbol = streams.decode(&litlen_table)?;
+                if symbol < END_OF_BLOCK {
+                    if out_pos == out_data.len() {
+                        return Err(DecompressionError::InsufficientSpace)?;
+                    }
+                    out_data[out_pos] = symbol as u8;
+                    out_pos += 1;
+                } else if symbol == END_OF_BLOCK {
+                    break;
+                } else {
+                    let slot = symbol - END_OF_BLOCK - 1;
+                    if slot >= LENGTH_BASE.len() {
+                        return Err(DecompressionError::BadData)?;
+                    }
+                    let length =
+                        (LENGTH_BASE[slot] + streams.pop_bits(LENGTH_EXTRA_BITS[slot])) as usize;
+                    if length > out_data.len() - out_pos {
+                        return Err(DecompressionError::InsufficientSpace)?;
+                    }
+                    copies[streams.idx] = Some(DeferredCopy { out_pos, length });
+                    out_pos += length;
+                }
+                streams.advance();
+            }
+
+            // run the copies still waiting for their offsets
+            for _ in 0..NUM_STREAMS {
+                if let Some(copy) = copies[streams.idx].take() {
+                    do_copy(&mut streams, &offset_table, out_data, copy)?;
+                }
+                streams.advance();
+            }
+
+            if is_final_block {
+                break;
+            }
+        }
+
+        Ok(out_pos)
+    }
+}
+
+/// Read the code lengths of a dynamic Huffman block.
+fn read_dynamic_tables(streams: &mut InputStreams) -> Result<(DecodeTable, DecodeTable)> {
+    let num_litlen_syms = streams.pop_bits(5) as usize + 257;
+    let num_offset_syms = streams.pop_bits(5) as usize + 1;
+    let num_explicit_precode_lens = streams.pop_bits(4) as usize + 4;
+    streams.ensure_bits();
+
+    let mut precode_lens = [0u8; NUM_PRECODE_SYMS];
+    for &symbol in &PRECODE_LENS_PERMUTATION[..num_explicit_precode_lens] {
+        precode_lens[symbol] = streams.pop_bits(3) as u8;
+        streams.advance();
+    }
+    let precode_table = DecodeTable::new(&precode_lens)?;
+
+    streams.reset();
+    let num_lens = num_litlen_syms + num_offset_syms;
+    // room for a run overflowing the last length, which is ignored like in libdeflate
+    let mut lens = vec![0u8; num_lens + 138];
+    let mut i = 0;
+    while i < num_lens {
+        let presym = streams.decode(&precode_table)?;
+        match presym {
+            0..=15 => {
+                lens[i] = presym as u8;
+                i += 1;
+            }
+            16 => {
+                // repeat the previous length 3 - 6 times
+                if i == 0 {
+                    Err(DecompressionError::BadData)?;
+                }
+                let count = 3 + streams.pop_bits(2) as usize;
+                let len = lens[i - 1];
+                lens[i..i + count].fill(len);
+                i += count;
+            }
+            17 => i += 3 + streams.pop_bits(3) as usize,
+            _ => i += 11 + streams.pop_bits(7) as usize,
+        }
+        streams.advance();
+    }
+
+    Ok((
+        DecodeTable::new(&lens[..num_litlen_syms])?,
+        DecodeTable::new(&lens[num_litlen_syms..num_lens])?,
+    ))
+}
+
+/// Read the offset of a deferred copy and copy the match.
+fn do_copy(
+    streams: &mut InputStreams,
+    offset_table: &DecodeTable,
+    out_data: &mut [u8],
+    copy: DeferredCopy,
+) -> Result<()> {
+    let slot = streams.decode(offset_table)?;
+    let offset = (OFFSET_BASE[slot] + streams.pop_bits(OFFSET_EXTRA_BITS[slot])) as usize;
+    if offset > copy.out_pos {
+        Err(DecompressionError::BadData)?;
+    }
+    // byte by byte, as the source may overlap the destination
+    for pos in copy.out_pos..copy.out_pos + copy.length {
+        out_data[pos] = out_data[pos - offset];
+    }
+    Ok(())
+}
+
+/// Writer side of the 32 interleaved bit streams.
+///
+/// The writer tracks how many bits the reader holds in each stream,
+/// and reserves the next packet of a stream at the point where the
+/// reader would refill it, so packets end up in reading order.
+struct OutputStreams {
+    out: Vec<u8>,
+    bitbuf: [u64; NUM_STREAMS],
+    bitcount: [u32; NUM_STREAMS],
+    /// Position of the packet being filled.
+    write_pos: [usize; NUM_STREAMS],
+    /// Position of the packet reserved after it, if any.
+    next_pos: [Option<usize>; NUM_STREAMS],
+    /// Bits the reader holds.
+    input_bitcount: [u32; NUM_STREAMS],
+    idx: usize,
+}
+
+impl OutputStreams {
+    fn new() -> Self {
+        let mut streams = Self {
+            out: Vec::new(),
+            bitbuf: [0; NUM_STREAMS],
+            bitcount: [0; NUM_STREAMS],
+            write_pos: [0; NUM_STREAMS],
+            next_pos: [None; NUM_STREAMS],
+            input_bitcount: [BITS_PER_PACKET; NUM_STREAMS],
+            idx: 0,
+        };
+        for n in 0..NUM_STREAMS {
+            streams.write_pos[n] = streams.reserve_packet();
+        }
+        streams
+    }
+
+    fn reserve_packet(&mut self) -> usize {
+        let pos = self.out.len();
+        self.out
+            .extend_from_slice(&[0; BITS_PER_PACKET as usize / 8]);
+        pos
+    }
+
+    fn write_packet(&mut self, idx: usize) {
+        let pos = self.write_pos[idx];
+        self.out[pos..pos + 4].copy_from_slice(&(self.bitbuf[idx] as u32).to_le_bytes());
+    }
+
+    fn add_bits(&mut self, bits: u32, num_bits: u32) {
+        let idx = self.idx;
+        self.bitbuf[idx] |= (bits as u64) << self.bitcount[idx];
+        self.bitcount[idx] += num_bits;
+        self.input_bitcount[idx] -= num_bits;
+
+        if self.bitcount[idx] >= LOW_WATERMARK_BITS {
+            self.write_packet(idx);
+            self.bitbuf[idx] >>= BITS_PER_PACKET;
+            self.bitcount[idx] -= BITS_PER_PACKET;
+            // at most 31 bits are added at once, so the reader has always
+            // asked for the next packet before this one fills up
+            self.write_pos[idx] = self.next_pos[idx]
+                .take()
+                .expect("next GDeflate packet is reserved");
+        }
+
+        if self.input_bitcount[idx] < LOW_WATERMARK_BITS && self.next_pos[idx].is_none() {
+            self.next_pos[idx] = Some(self.reserve_packet());
+            self.input_bitcount[idx] += BITS_PER_PACKET;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.idx = 0;
+    }
+
+    fn advance(&mut self) {
+        self.idx = (self.idx + 1) % NUM_STREAMS;
+    }
+
+    /// Write the bits left in every stream.
+    fn finish(mut self) -> Vec<u8> {
+        for idx in 0..NUM_STREAMS {
+            self.write_packet(idx);
+        }
+        self.out
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Item {
+    Literal(u8),
+    Match { length: usize, offset: usize },
+}
+
+fn length_slot(length: usize) -> usize {
+    // slot 28 is the Deflate64 long match slot, never emitted
+    LENGTH_BASE[..28].partition_point(|&base| base as usize <= length) - 1
+}
+
+fn offset_slot(offset: usize) -> usize {
+    OFFSET_BASE.partition_point(|&base| base as usize <= offset) - 1
+}
+
+/// Hash chain match finder over a single tile.
+struct MatchFinder {
+    head: Vec<u32>,
+    prev: Vec<u32>,
+}
+
+impl MatchFinder {
+    const HASH_BITS: u32 = 15;
+    const NONE: u32 = u32::MAX;
+
+    fn new(len: usize) -> Self {
+        Self {
+            head: vec![Self::NONE; 1 << Self::HASH_BITS],
+            prev: vec![Self::NONE; len],
+        }
+    }
+
+    fn hash(data: &[u8], pos: usize) -> usize {
+        let bytes = u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], 0]);
+        (bytes.wrapping_mul(0x1E35_A7BD) >> (32 - Self::HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, data: &[u8], pos: usize) {
+        if pos + MIN_MATCH_LEN > data.len() {
+            return;
+        }
+        let hash = Self::hash(data, pos);
+        self.prev[pos] = self.head[hash];
+        self.head[hash] = pos as u32;
+    }
+
+    /// Longest earlier match at `pos`, as `(length, offset)`.
+    fn longest_match(&self, data: &[u8], pos: usize, params: MatchParams) -> (usize, usize) {
+        let max_len = MAX_MATCH_LEN.min(data.len() - pos);
+        if max_len < MIN_MATCH_LEN {
+            return (0, 0);
+        }
+
+        let mut best = (MIN_MATCH_LEN - 1, 0);
+        let mut candidate = self.head[Self::hash(data, pos)];
+        for _ in 0..params.max_search_depth {
+            if candidate == Self::NONE {
+                break;
+            }
+            let start = candidate as usize;
+            if data[start + best.0] == data[pos + best.0] {
+                let length = data[start..start + max_len]
+                    .iter()
+                    .zip(&data[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best.0 {
+                    best = (length, pos - start);
+                    if length >= params.nice_match_length.min(max_len) {
+                        break;
+                    }
+                }
+            }
+            candidate = self.prev[start];
+        }
+
+        if best.0 >= MIN_MATCH_LEN {
+            best
+        } else {
+            (0, 0)
+        }
+    }
+}
+
+/// Match finder settings, following libdeflate's levels.
+#[derive(Debug, Clone, Copy)]
+struct MatchParams {
+    max_search_depth: usize,
+    nice_match_length: usize,
+    lazy: bool,
+}
+
+impl MatchParams {
+    fn from_level(level: CompressionLevel) -> Option<Self> {
+        let (max_search_depth, nice_match_length, lazy) = match level {
+            CompressionLevel::None => return None,
+            CompressionLevel::Level1 => (2, 8, false),
+            CompressionLevel::Level2 => (6, 10, false),
+            CompressionLevel::Level3 => (12, 14, false),
+            CompressionLevel::Level4 => (24, 24, false),
+            CompressionLevel::Level5 => (20, 30, true),
+            CompressionLevel::Level6 => (40, 65, true),
+            CompressionLevel::Level7 => (100, 130, true),
+            CompressionLevel::Level8 => (150, 200, true),
+            _ => (200, MAX_MATCH_LEN, true),
+        };
+        Some(Self {
+            max_search_depth,
+            nice_match_length,
+            lazy,
+        })
+    }
+}
+
+/// Split a tile into literals and matches.
+fn find_items(data: &[u8], params: MatchParams) -> Vec<Item> {
+    let mut match_finder = MatchFinder::new(data.len());
+    let mut items = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let (mut length, mut offset) = match_finder.longest_match(data, pos, params);
+        match_finder.insert(data, pos);
+
+        // emit a literal instead if the next position has a longer match
+        while params.lazy && length >= MIN_MATCH_LEN && length < params.nice_match_length {
+            let (next_length, next_offset) = match_finder.longest_match(data, pos + 1, params);
+            if next_length <= length {
+                break;
+            }
+            items.push(Item::Literal(data[pos]));
+            pos += 1;
+            match_finder.insert(data, pos);
+            (length, offset) = (next_length, next_offset);
+        }
+
+        if length >= MIN_MATCH_LEN {
+            items.push(Item::Match { length, offset });
+            for skipped in pos + 1..pos + length {
+                match_finder.insert(data, skipped);
+            }
+            pos += length;
+        } else {
+            items.push(Item::Literal(data[pos]));
+            pos += 1;
+        }
+    }
+    items
+}
+
+/// Length-limited Huffman codeword lengths for symbol frequencies.
+fn huffman_lens(freqs: &[u32], max_len: u32) -> Vec<u8> {
+    let mut lens = vec![0u8; freqs.len()];
+    // a complete code needs at least two symbols
+    let mut symbols: Vec<usize> = (0..freqs.len()).filter(|&sym| freqs[sym] > 0).collect();
+    for sym in 0..freqs.len() {
+        if symbols.len() >= 2 {
+            break;
+        }
+        if !symbols.contains(&sym) {
+            symbols.push(sym);
+        }
+    }
+    symbols.sort_by_key(|&sym| (freqs[sym], sym));
+
+    // build the tree with two queues, leaves sorted by frequency
+    let num_leaves = symbols.len();
+    let mut weights: Vec<u64> = symbols.iter().map(|&sym| freqs[sym] as u64).collect();
+    let mut parents = vec![0usize; 2 * num_leaves - 1];
+    let (mut next_leaf, mut next_node) = (0, num_leaves);
+    for node in num_leaves..2 * num_leaves - 1 {
+        let mut pick = || {
+            if next_leaf < num_leaves
+                && (next_node >= node || weights[next_leaf] <= weights[next_node])
+            {
+                next_leaf += 1;
+                next_leaf - 1
+            } else {
+                next_node += 1;
+                next_node - 1
+            }
+        };
+        let (a, b) = (pick(), pick());
+        weights.push(weights[a] + weights[b]);
+        parents[a] = node;
+        parents[b] = node;
+    }
+
+    // count the leaf depths, clamped to the limit
+    let root = 2 * num_leaves - 2;
+    let mut depths = vec![0u32; 2 * num_leaves - 1];
+    let mut len_counts = vec![0u32; max_len as usize + 1];
+    for node in (0..root).rev() {
+        depths[node] = depths[parents[node]] + 1;
+    }
+    for &depth in &depths[..num_leaves] {
+        len_counts[depth.min(max_len) as usize] += 1;
+    }
+
+    // shorten the clamped codes until the code is complete again
+    let kraft = |len_counts: &[u32]| -> u32 {
+        (1..=max_len)
+            .map(|len| len_counts[len as usize] << (max_len - len))
+            .sum()
+    };
+    let mut total = kraft(&len_counts);
+    while total > 1 << max_len {
+        len_counts[max_len as usize] -= 1;
+        if let Some(len) = (1..max_len as usize)
+            .rev()
+            .find(|&len| len_counts[len] != 0)
+        {
+            len_counts[len] -= 1;
+            len_counts[len + 1] += 2;
+        }
+        total -= 1;
+    }
+
+    // most frequent symbols get the shortest codewords
+    let mut sorted = symbols.iter().rev();
+    for len in 1..=max_len {
+        for _ in 0..len_counts[len as usize] {
+            lens[*sorted.next().unwrap()] = len as u8;
+        }
+    }
+    lens
+}
+
+/// Huffman codes of a block.
+struct Codes {
+    litlen_lens: Vec<u8>,
+    litlen_codewords: Vec<u16>,
+    offset_lens: Vec<u8>,
+    offset_codewords: Vec<u16>,
+}
+
+impl Codes {
+    fn new(litlen_lens: &[u8], offset_lens: &[u8]) -> Self {
+        Self {
+            litlen_lens: litlen_lens.to_vec(),
+            litlen_codewords: canonical_codewords(litlen_lens),
+            offset_lens: offset_lens.to_vec(),
+            offset_codewords: canonical_codewords(offset_lens),
+        }
+    }
+
+    /// Cost of the literals, matches and end-of-block symbol in bits.
+    fn cost(&self, litlen_freqs: &[u32], offset_freqs: &[u32]) -> u32 {
+        let litlen_cost: u32 = litlen_freqs
+            .iter()
+            .enumerate()
+            .map(|(sym, &freq)| {
+                let extra = sym
+                    .checked_sub(END_OF_BLOCK + 1)
+                    .and_then(|slot| LENGTH_EXTRA_BITS.get(slot))
+                    .map_or(0, |&bits| bits);
+                freq * (self.litlen_lens[sym] as u32 + extra)
+            })
+            .sum();
+        let offset_cost: u32 = offset_freqs
+            .iter()
+            .enumerate()
+            .map(|(sym, &freq)| freq * (self.offset_lens[sym] as u32 + OFFSET_EXTRA_BITS[sym]))
+            .sum();
+        litlen_cost + offset_cost
+    }
+}
+
+/// Dynamic Huffman block header: the run-length encoded codeword lengths.
+struct DynamicHeader {
+    num_litlen_syms: usize,
+    num_offset_syms: usize,
+    num_explicit_precode_lens: usize,
+    precode_lens: Vec<u8>,
+    precode_codewords: Vec<u16>,
+    /// Precode symbols with their extra bits.
+    precode_items: Vec<(usize, u32)>,
+}
+
+impl DynamicHeader {
+    fn new(codes: &Codes) -> Self {
+        let num_litlen_syms = (END_OF_BLOCK + 1).max(
+            codes
+                .litlen_lens
+                .iter()
+                .rposition(|&len| len != 0)
+                .unwrap_or(0)
+                + 1,
+        );
+        let num_offset_syms = codes
+            .offset_lens
+            .iter()
+            .rposition(|&len| len != 0)
+            .unwrap_or(0)
+            + 1;
+
+        let lens: Vec<u8> = codes.litlen_lens[..num_litlen_syms]
+            .iter()
+            .chain(&codes.offset_lens[..num_offset_syms])
+            .copied()
+            .collect();
+        let precode_items = precode_items(&lens);
+
+        let mut precode_freqs = [0u32; NUM_PRECODE_SYMS];
+        for &(presym, _) in &precode_items {
+            precode_freqs[presym] += 1;
+        }
+        let precode_lens = huffman_lens(&precode_freqs, MAX_PRE_CODEWORD_LEN);
+        let num_explicit_precode_lens = PRECODE_LENS_PERMUTATION
+            .iter()
+            .rposition(|&sym| precode_lens[sym] != 0)
+            .map_or(4, |pos| (pos + 1).max(4));
+
+        Self {
+            num_litlen_syms,
+            num_offset_syms,
+            num_explicit_precode_lens,
+            precode_codewords: canonical_codewords(&precode_lens),
+            precode_lens,
+            precode_items,
+        }
+    }
+
+    fn cost(&self) -> u32 {
+        let items_cost: u32 = self
+            .precode_items
+            .iter()
+            .map(|&(presym, _)| self.precode_lens[presym] as u32 + precode_extra_bits(presym))
+            .sum();
+        5 + 5 + 4 + 3 * self.num_explicit_precode_lens as u32 + items_cost
+    }
+
+    fn write(&self, streams: &mut OutputStreams) {
+        streams.reset();
+        streams.add_bits(self.num_litlen_syms as u32 - 257, 5);
+        streams.add_bits(self.num_offset_syms as u32 - 1, 5);
+        streams.add_bits(self.num_explicit_precode_lens as u32 - 4, 4);
+
+        for &sym in &PRECODE_LENS_PERMUTATION[..self.num_explicit_precode_lens] {
+            streams.add_bits(self.precode_lens[sym] as u32, 3);
+            streams.advance();
+        }
+
+        streams.reset();
+        for &(presym, extra) in &self.precode_items {
+            streams.add_bits(
+                self.precode_codewords[presym] as u32,
+                self.precode_lens[presym] as u32,
+            );
+            streams.add_bits(extra, precode_extra_bits(presym));
+            streams.advance();
+        }
+    }
+}
+
+fn precode_extra_bits(presym: usize) -> u32 {
+    match presym {
+        16 => 2,
+        17 => 3,
+        18 => 7,
+        _ => 0,
+    }
+}
+
+/// Run-length encode codeword lengths with the precode symbols.
+fn precode_items(lens: &[u8]) -> Vec<(usize, u32)> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    while start < lens.len() {
+        let len = lens[start];
+        let run_len = lens[start..].iter().take_while(|&&l| l == len).count();
+        let mut remaining = run_len;
+
+        if len == 0 {
+            while remaining >= 11 {
+                let count = remaining.min(138);
+                items.push((18, (count - 11) as u32));
+                remaining -= count;
+            }
+            if remaining >= 3 {
+                items.push((17, (remaining - 3) as u32));
+                remaining = 0;
+            }
+        } else if remaining >= 4 {
+            items.push((len as usize, 0));
+            remaining -= 1;
+            while remaining >= 3 {
+                let count = remaining.min(6);
+                items.push((16, (count - 3) as u32));
+                remaining -= count;
+            }
+        }
+        items.extend(std::iter::repeat_n((len as usize, 0), remaining));
+
+        start += run_len;
+    }
+    items
+}
+
+fn write_block_header(streams: &mut OutputStreams, is_final_block: bool, block_type: u32) {
+    streams.reset();
+    streams.add_bits(is_final_block as u32, 1);
+    streams.add_bits(block_type, 2);
+}
+
+fn write_uncompressed_blocks(streams: &mut OutputStreams, data: &[u8]) {
+    let mut blocks = data.chunks(MAX_UNCOMPRESSED_BLOCK_LEN).peekable();
+    while let Some(block) = blocks.next() {
+        write_block_header(streams, blocks.peek().is_none(), BLOCKTYPE_UNCOMPRESSED);
+        streams.add_bits(block.len() as u32, 16);
+        for &byte in block {
+            streams.add_bits(byte as u32, 8);
+            streams.advance();
+        }
+    }
+}
+
+/// Write the items of a Huffman block, deferring each match offset
+/// to the next visit of the stream holding its length.
+fn write_items(streams: &mut OutputStreams, codes: &Codes, items: &[Item]) {
+    let mut copies = [None::<(u32, u32)>; NUM_STREAMS];
+    let write_pending_copies = |streams: &mut OutputStreams, copies: &mut [Option<(u32, u32)>]| {
+        while let Some((bits, num_bits)) = copies[streams.idx].take() {
+            streams.add_bits(bits, num_bits);
+            streams.advance();
+        }
+    };
+
+    streams.reset();
+    for &item in items {
+        write_pending_copies(streams, &mut copies);
+        match item {
+            Item::Literal(byte) => streams.add_bits(
+                codes.litlen_codewords[byte as usize] as u32,
+                codes.litlen_lens[byte as usize] as u32,
+            ),
+            Item::Match { length, offset } => {
+                let slot = length_slot(length);
+                let sym = END_OF_BLOCK + 1 + slot;
+                streams.add_bits(
+                    codes.litlen_codewords[sym] as u32,
+                    codes.litlen_lens[sym] as u32,
+                );
+                streams.add_bits(length as u32 - LENGTH_BASE[slot], LENGTH_EXTRA_BITS[slot]);
+
+                let slot = offset_slot(offset);
+                let codeword_len = codes.offset_lens[slot] as u32;
+                copies[streams.idx] = Some((
+                    codes.offset_codewords[slot] as u32
+                        | (offset as u32 - OFFSET_BASE[slot]) << codeword_len,
+                    codeword_len + OFFSET_EXTRA_BITS[slot],
+                ));
+            }
+        }
+        streams.advance();
+    }
+    write_pending_copies(streams, &mut copies);
+
+    streams.add_bits(
+        codes.litlen_codewords[END_OF_BLOCK] as u32,
+        codes.litlen_lens[END_OF_BLOCK] as u32,
+    );
+    streams.advance();
+
+    // the reader runs the remaining copies in stream order
+    // after the end-of-block symbol
+    for _ in 0..NUM_STREAMS {
+        if let Some((bits, num_bits)) = copies[streams.idx].take() {
+            streams.add_bits(bits, num_bits);
+        }
+        streams.advance();
+    }
+}
+
+/// GDeflate tile compressor.
+pub(super) struct TileCompressor {
+    params: Option<MatchParams>,
+}
+
+impl TileCompressor {
+    pub fn new(level: CompressionLevel) -> Result<Self> {
+        Ok(Self {
+            params: MatchParams::from_level(level),
+        })
+    }
+
+    /// Compress a single tile of at most [`KDEFAULT_TILE_SIZE`] bytes
+    /// as one block, picking the cheapest block type.
+    pub fn compress_tile(&mut self, input: &[u8]) -> Result<Vec<u8>> {
+        if input.is_empty() || input.len() > KDEFAULT_TILE_SIZE {
+            Err(CompressionError::CompressionFailed)?
+        }
+
+        let mut streams = OutputStreams::new();
+        let Some(params) = self.params else {
+            write_uncompressed_blocks(&mut streams, input);
+            return Ok(streams.finish());
+        };
+
+        let items = find_items(input, params);
+        let mut litlen_freqs = [0u32; NUM_LITLEN_SYMS];
+        let mut offset_freqs = [0u32; NUM_OFFSET_SYMS];
+        for &item in &items {
+            match item {
+                Item::Literal(byte) => litlen_freqs[byte as usize] += 1,
+                Item::Match { length, offset } => {
+                    litlen_freqs[END_OF_BLOCK + 1 + length_slot(length)] += 1;
+                    offset_freqs[offset_slot(offset)] += 1;
+                }
+            }
+        }
+        litlen_freqs[END_OF_BLOCK] += 1;
+
+        let dynamic_codes = Codes::new(
+            &huffman_lens(&litlen_freqs, MAX_CODEWORD_LEN),
+            &huffman_lens(&offset_freqs, MAX_CODEWORD_LEN),
+        );
+        let dynamic_header = DynamicHeader::new(&dynamic_codes);
+        let (static_litlen_lens, static_offset_lens) = static_lens();
+        let static_codes = Codes::new(&static_litlen_lens, &static_offset_lens);
+
+        let dynamic_cost = dynamic_header.cost() + dynamic_codes.cost(&litlen_freqs, &offset_freqs);
+        let static_cost = static_codes.cost(&litlen_freqs, &offset_freqs);
+        let num_uncompressed_blocks = input.len().div_ceil(MAX_UNCOMPRESSED_BLOCK_LEN) as u32;
+        let uncompressed_cost = 16 + 40 * (num_uncompressed_blocks - 1) + 8 * input.len() as u32;
+
+        if dynamic_cost < static_cost.min(uncompressed_cost) {
+            write_block_header(&mut streams, true, BLOCKTYPE_DYNAMIC_HUFFMAN);
+            dynamic_header.write(&mut streams);
+            write_items(&mut streams, &dynamic_codes, &items);
+        } else if static_cost < uncompressed_cost {
+            write_block_header(&mut streams, true, BLOCKTYPE_STATIC_HUFFMAN);
+            write_items(&mut streams, &static_codes, &items);
+        } else {
+            write_uncompressed_blocks(&mut streams, input);
+        }
+        Ok(streams.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(data: &[u8], level: CompressionLevel) {
+        let compressed = TileCompressor::new(level)
+            .unwrap()
+            .compress_tile(data)
+            .unwrap();
+        let mut out_data = vec![0u8; data.len()];
+        let out_nbytes = TileDecompressor::new()
+            .unwrap()
+            .decompress_tile(&compressed, &mut out_data)
+            .unwrap();
+        assert_eq!(out_nbytes, data.len());
+        assert_eq!(out_data, data, "{level:?}");
+    }
+
+    #[test]
+    fn test_pure_tile_rw() {
+        let text: Vec<u8> = b"GDeflate tile, GDeflate stream. "
+            .iter()
+            .cycle()
+            .take(KDEFAULT_TILE_SIZE)
+            .copied()
+            .collect();
+        let noise: Vec<u8> = (0u32..KDEFAULT_TILE_SIZE as u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        let mixed: Vec<u8> = text[..20000]
+            .iter()
+            .chain(&noise[..20000])
+            .chain(&[0u8; 20000])
+            .copied()
+            .collect();
+
+        for level in [
+            CompressionLevel::None,
+            CompressionLevel::Level1,
+            CompressionLevel::Level6,
+            CompressionLevel::Level12,
+        ] {
+            for data in [&text[..], &noise, &mixed, &text[..1], &[7; 300]] {
+                roundtrip(data, level);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pure_huffman_lens() {
+        // a skewed distribution that needs more than 7 bits unlimited
+        let freqs: Vec<u32> = (0..19).map(|i| 1 << i).collect();
+        let lens = huffman_lens(&freqs, MAX_PRE_CODEWORD_LEN);
+        assert!(lens.iter().all(|&len| (1..=7).contains(&len)));
+        let kraft: u32 = lens.iter().map(|&len| 1 << (7 - len)).sum();
+        assert_eq!(kraft, 1 << 7);
+
+        // single symbol codes are padded to stay complete
+        assert_eq!(huffman_lens(&[0, 0, 5], 15), vec![1, 0, 1]);
+    }
+
+    #[test]
+    fn test_pure_tile_bad_data() {
+        let data = vec![1u8; 1000];
+        let compressed = TileCompressor::new(CompressionLevel::Level6)
+            .unwrap()
+            .compress_tile(&data)
+            .unwrap();
+        let mut decompressor = TileDecompressor::new().unwrap();
+
+        // output too small
+        let mut out_data = vec![0u8; 999];
+        assert!(
+            decompressor
+                .decompress_tile(&compressed, &mut out_data)
+                .is_err()
+        );
+
+        // garbage and empty input must fail without panicking
+        let mut out_data = vec![0u8; 1000];
+        assert!(decompressor.decompress_tile(&[], &mut out_data).is_err());
+        for seed in 0..64u32 {
+            let garbage: Vec<u8> = (0..256u32)
+                .map(|i| (i ^ seed).wrapping_mul(0x9E37_79B9).rotate_left(seed) as u8)
+                .collect();
+            let _ = decompressor.decompress_tile(&garbage, &mut out_data);
+        }
+    }
+}
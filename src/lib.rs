@@ -1,6 +1,12 @@
+#![cfg_attr(not(feature = "libdeflate"), forbid(unsafe_code))]
+
 pub mod error;
 pub mod format;
+pub mod gdf;
 pub mod tex;
+pub mod version;
 
-mod gdf;
 mod macros;
+mod swizzle;
+
+pub use gdf::{CompressionLevel, TileSize};
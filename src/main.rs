@@ -0,0 +1,420 @@
+//! re-tex command-line tool.
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write as _};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+use ddsfile::Dds;
+use re_tex::CompressionLevel;
+use re_tex::format::TexFormat;
+use re_tex::tex::{CompressOptions, ImageEncodeOptions, Tex};
+use re_tex::version::{Game, TexVersion};
+
+type CliResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser)]
+#[command(version, about = "Read, write and convert RE Engine Tex files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the header and mip table of Tex files.
+    Info {
+        /// Input files, glob patterns are supported.
+        #[arg(required = true)]
+        inputs: Vec<String>,
+    },
+    /// Convert Tex files to DDS, `<name>.tex.<version>` to `<name>.tex.<version>.dds`.
+    ToDds {
+        #[command(flatten)]
+        io: IoArgs,
+    },
+    /// Convert the first layer of Tex files to PNG.
+    ToPng {
+        #[command(flatten)]
+        io: IoArgs,
+        /// Mipmap to convert.
+        #[arg(long, default_value_t = 0)]
+        mip: usize,
+    },
+    /// Convert the first layer of ASTC Tex files to `.astc`.
+    ToAstc {
+        #[command(flatten)]
+        io: IoArgs,
+        /// Mipmap to convert.
+        #[arg(long, default_value_t = 0)]
+        mip: usize,
+    },
+    /// Create Tex files from DDS.
+    FromDds {
+        #[command(flatten)]
+        io: IoArgs,
+        #[command(flatten)]
+        target: TargetArgs,
+    },
+    /// Create Tex files from PNG or other images.
+    FromPng {
+        #[command(flatten)]
+        io: IoArgs,
+        #[command(flatten)]
+        target: TargetArgs,
+        /// Tex format to encode, e.g. Bc7UnormSrgb or 0x63.
+        #[arg(long, value_parser = parse_format)]
+        format: TexFormat,
+        /// Number of mipmaps to generate, a full chain if omitted.
+        #[arg(long)]
+        mipmaps: Option<u32>,
+    },
+    /// Decompress GDeflate mipmaps of Tex files.
+    Decompress {
+        #[command(flatten)]
+        io: IoArgs,
+    },
+    /// Compress mipmaps of Tex files with GDeflate.
+    Compress {
+        #[command(flatten)]
+        io: IoArgs,
+        /// GDeflate compression level.
+        #[arg(long, default_value_t = 12, value_parser = clap::value_parser!(u8).range(0..=12))]
+        level: u8,
+        /// Keep mipmaps uncompressed when GDeflate does not make them smaller.
+        #[arg(long)]
+        only_if_smaller: bool,
+    },
+}
+
+#[derive(Args)]
+struct IoArgs {
+    /// Input files, glob patterns are supported.
+    #[arg(required = true)]
+    inputs: Vec<String>,
+    /// Output directory. Defaults to the directory of each input,
+    /// `decompress` and `compress` then overwrite the input.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+impl IoArgs {
+    /// Output path of `input` with the file name `file_name`.
+    fn output_path(&self, input: &Path, file_name: &str) -> PathBuf {
+        match &self.output {
+            Some(dir) => dir.join(file_name),
+            None => input.with_file_name(file_name),
+        }
+    }
+}
+
+#[derive(Args)]
+struct TargetArgs {
+    /// Tex version to create. Detected from `<name>.tex.<version>.<ext>` inputs if omitted.
+    #[arg(long, conflicts_with = "game")]
+    tex_version: Option<u32>,
+    /// Game to create Tex files for, e.g. MHWilds.
+    #[arg(long)]
+    game: Option<Game>,
+}
+
+impl TargetArgs {
+    /// Get the target version and output file name for `input`.
+    fn resolve(&self, input: &Path) -> CliResult<(TexVersion, String)> {
+        let stem = file_stem(input)?;
+        let detected = TexVersion::from_path(stem);
+        let version = match (self.tex_version, self.game) {
+            (Some(version), _) => TexVersion(version),
+            (None, Some(game)) => game.tex_version(),
+            (None, None) => detected.ok_or_else(|| {
+                format!("Can't detect the Tex version of {input:?}, use --tex-version or --game")
+            })?,
+        };
+        // `name.tex.<version>` or plain `name`
+        let name = match detected {
+            Some(_) => stem.rsplitn(3, '.').nth(2).unwrap_or(stem),
+            None => stem,
+        };
+        Ok((version, format!("{name}.tex.{version}")))
+    }
+}
+
+fn parse_format(s: &str) -> Result<TexFormat, String> {
+    let format = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(TexFormat::from_repr),
+        None => s.parse().ok(),
+    };
+    format.ok_or_else(|| format!("unknown Tex format: {s}"))
+}
+
+fn compression_level(level: u8) -> CompressionLevel {
+    match level {
+        0 => CompressionLevel::None,
+        1 => CompressionLevel::Level1,
+        2 => CompressionLevel::Level2,
+        3 => CompressionLevel::Level3,
+        4 => CompressionLevel::Level4,
+        5 => CompressionLevel::Level5,
+        6 => CompressionLevel::Level6,
+        7 => CompressionLevel::Level7,
+        8 => CompressionLevel::Level8,
+        9 => CompressionLevel::Level9,
+        10 => CompressionLevel::Level10,
+        11 => CompressionLevel::Level11,
+        _ => CompressionLevel::Level12,
+    }
+}
+
+fn file_name(path: &Path) -> CliResult<&str> {
+    Ok(path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Invalid file name: {path:?}"))?)
+}
+
+fn file_stem(path: &Path) -> CliResult<&str> {
+    Ok(path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| format!("Invalid file name: {path:?}"))?)
+}
+
+/// Expand glob patterns, keeping inputs that exist as-is.
+fn expand_inputs(patterns: &[String]) -> CliResult<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        let literal = Path::new(pattern);
+        if literal.is_file() {
+            paths.push(literal.to_path_buf());
+            continue;
+        }
+        let matches = glob::glob(pattern)?
+            .filter_map(|entry| entry.ok())
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            return Err(format!("No files match {pattern:?}").into());
+        }
+        paths.extend(matches);
+    }
+    Ok(paths)
+}
+
+fn read_tex(path: &Path) -> CliResult<Tex> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let tex = Tex::from_reader(&mut reader)?;
+    if let Some(version) = TexVersion::from_path(path)
+        && version != tex.header.tex_version()
+    {
+        eprintln!(
+            "warning: {path:?} has Tex version {}, not {version}",
+            tex.header.version
+        );
+    }
+    Ok(tex)
+}
+
+fn write_tex(tex: Tex, path: &Path) -> CliResult<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    tex.write_to(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn print_info(path: &Path) -> CliResult<()> {
+    let tex = read_tex(path)?;
+    let version = tex.header.tex_version();
+    let games = version
+        .games()
+        .iter()
+        .map(|game| game.title())
+        .collect::<Vec<_>>();
+
+    println!("{}", path.display());
+    if games.is_empty() {
+        println!("version: {version} (unknown)");
+    } else {
+        println!("version: {version} ({})", games.join(", "));
+    }
+    println!("{:#?}", tex.header);
+    let mipmap_count = tex.header.mipmap_count.max(1) as usize;
+    for (idx, mip_data) in tex.mip_datas.iter().enumerate() {
+        let entry = &mip_data.entry;
+        let info = &mip_data.compression_info;
+        println!(
+            "layer {} mip {}: offset={} scanline_length={} uncompressed_size={} \
+             compressed_offset={} compressed_size={} gdeflate={}",
+            idx / mipmap_count,
+            idx % mipmap_count,
+            entry.offset(),
+            entry.scanline_length(),
+            entry.uncompressed_size(),
+            info.compressed_offset(),
+            info.compressed_size(),
+            mip_data.is_compressed(),
+        );
+    }
+    Ok(())
+}
+
+fn run(command: &Command, input: &Path) -> CliResult<Option<PathBuf>> {
+    let output = match command {
+        Command::Info { .. } => {
+            print_info(input)?;
+            return Ok(None);
+        }
+        Command::ToDds { io } => {
+            let tex = read_tex(input)?;
+            let output = io.output_path(input, &format!("{}.dds", file_name(input)?));
+            let dds = tex.to_dds(tex.header.mipmap_count as usize)?;
+            dds.write(&mut BufWriter::new(File::create(&output)?))?;
+            output
+        }
+        Command::ToPng { io, mip } => {
+            let tex = read_tex(input)?;
+            let output = io.output_path(input, &format!("{}.png", file_name(input)?));
+            tex.to_rgba_image(*mip)?.save(&output)?;
+            output
+        }
+        Command::ToAstc { io, mip } => {
+            let tex = read_tex(input)?;
+            let output = io.output_path(input, &format!("{}.astc", file_name(input)?));
+            fs::write(&output, tex.to_astc(0, *mip)?)?;
+            output
+        }
+        Command::FromDds { io, target } => {
+            let (version, name) = target.resolve(input)?;
+            let dds = Dds::read(BufReader::new(File::open(input)?))?;
+            let output = io.output_path(input, &name);
+            write_tex(Tex::from_dds(&dds, version)?, &output)?;
+            output
+        }
+        Command::FromPng {
+            io,
+            target,
+            format,
+            mipmaps,
+        } => {
+            let (version, name) = target.resolve(input)?;
+            let image = image::open(input)?.to_rgba8();
+            let mut options = ImageEncodeOptions::new(version);
+            if let Some(mipmaps) = mipmaps {
+                options.mipmaps = image_dds::Mipmaps::GeneratedExact(*mipmaps);
+            }
+            let output = io.output_path(input, &name);
+            write_tex(Tex::from_rgba_image(&image, *format, &options)?, &output)?;
+            output
+        }
+        Command::Decompress { io } => {
+            let mut tex = read_tex(input)?;
+            tex.batch_decompress()?;
+            let output = io.output_path(input, file_name(input)?);
+            write_tex(tex, &output)?;
+            output
+        }
+        Command::Compress {
+            io,
+            level,
+            only_if_smaller,
+        } => {
+            let mut tex = read_tex(input)?;
+            let options = CompressOptions {
+                only_if_smaller: *only_if_smaller,
+                ..CompressOptions::new(compression_level(*level))
+            };
+            tex.batch_compress_with(&options)?;
+            let output = io.output_path(input, file_name(input)?);
+            write_tex(tex, &output)?;
+            output
+        }
+    };
+    Ok(Some(output))
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let (patterns, output_dir) = match &cli.command {
+        Command::Info { inputs } => (inputs, None),
+        Command::ToDds { io }
+        | Command::ToPng { io, .. }
+        | Command::ToAstc { io, .. }
+        | Command::FromDds { io, .. }
+        | Command::FromPng { io, .. }
+        | Command::Decompress { io }
+        | Command::Compress { io, .. } => (&io.inputs, io.output.as_ref()),
+    };
+
+    let inputs = match expand_inputs(patterns) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(dir) = output_dir
+        && let Err(e) = fs::create_dir_all(dir)
+    {
+        eprintln!("error: Can't create {dir:?}: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    let mut failed = 0;
+    for input in &inputs {
+        match run(&cli.command, input) {
+            Ok(Some(output)) => println!("{} -> {}", input.display(), output.display()),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("error: {}: {e}", input.display());
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{failed} of {} files failed", inputs.len());
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_resolve() {
+        let target = TargetArgs {
+            tex_version: None,
+            game: None,
+        };
+        let (version, name) = target
+            .resolve(Path::new("dir/ch04_ALBD.tex.241106027.dds"))
+            .unwrap();
+        assert_eq!(version, TexVersion::MH_WILDS);
+        assert_eq!(name, "ch04_ALBD.tex.241106027");
+        assert!(target.resolve(Path::new("ch04_ALBD.png")).is_err());
+
+        let target = TargetArgs {
+            tex_version: None,
+            game: Some(Game::RE4),
+        };
+        let (version, name) = target
+            .resolve(Path::new("ch04_ALBD.tex.241106027.dds"))
+            .unwrap();
+        assert_eq!(version, TexVersion::RE4);
+        assert_eq!(name, "ch04_ALBD.tex.143221013");
+        let (_, name) = target.resolve(Path::new("ch04_ALBD.png")).unwrap();
+        assert_eq!(name, "ch04_ALBD.tex.143221013");
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(parse_format("bc7unormsrgb"), Ok(TexFormat::Bc7UnormSrgb));
+        assert_eq!(parse_format("0x63"), Ok(TexFormat::Bc7UnormSrgb));
+        assert!(parse_format("bc8").is_err());
+    }
+}
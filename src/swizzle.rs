@@ -0,0 +1,204 @@
+//! Tegra X1 block linear swizzling, used by textures with `swizzle_control == 1`.
+//!
+//! Reference: <https://github.com/ScanMountGoat/tegra_swizzle>
+
+use tegra_swizzle::BlockHeight;
+use tegra_swizzle::swizzle::{deswizzle_block_linear, swizzle_block_linear, swizzled_mip_size};
+
+use crate::error::{Error, Result};
+
+/// Block linear layout of a swizzled Tex.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BlockLinear {
+    /// Block height of the first mip, in GOBs.
+    block_height: BlockHeight,
+    /// Block depth of the first mip, in GOBs.
+    block_depth: u32,
+}
+
+impl BlockLinear {
+    /// Layout of a texture `depth` slices deep, with blocks of the first mip
+    /// 2^`block_height_log2` GOBs tall.
+    ///
+    /// The block depth is picked from `depth` the same way as the driver.
+    pub fn new(block_height_log2: u8, depth: u32) -> Result<Self> {
+        let block_height = 1u32
+            .checked_shl(block_height_log2 as u32)
+            .and_then(BlockHeight::new)
+            .ok_or_else(|| {
+                Error::InvalidTexData(format!(
+                    "Invalid swizzle block height: 2^{block_height_log2}"
+                ))
+            })?;
+        Ok(Self {
+            block_height,
+            block_depth: block_depth(depth),
+        })
+    }
+
+    /// Create from the `swizzle_height_depth` and `swizzle_width` header fields
+    /// of a texture `depth` slices deep.
+    ///
+    /// `swizzle_height_depth` holds the log2 of the block height in GOBs in the
+    /// low nibble and of the block depth in the high nibble, `swizzle_width` the
+    /// log2 of the block width. Blocks are always 1 GOB wide, and the block
+    /// depth must be the one the driver picks for `depth`, since the swizzler
+    /// derives it from the mip depth.
+    pub fn from_header(swizzle_height_depth: u8, swizzle_width: u8, depth: u32) -> Result<Self> {
+        if swizzle_width != 0 {
+            return Err(Error::InvalidTexData(format!(
+                "Unsupported swizzle block width: 2^{swizzle_width}"
+            )));
+        }
+        let this = Self::new(swizzle_height_depth & 0xF, depth)?;
+        let block_depth_log2 = swizzle_height_depth >> 4;
+        if 1 << block_depth_log2 != this.block_depth {
+            return Err(Error::InvalidTexData(format!(
+                "Unsupported swizzle block depth for {depth} slices: 2^{block_depth_log2}"
+            )));
+        }
+        Ok(this)
+    }
+
+    /// Get the `swizzle_height_depth` and `swizzle_width` header fields.
+    pub fn header_values(&self) -> (u8, u8) {
+        let block_height_log2 = (self.block_height as u32).trailing_zeros() as u8;
+        let block_depth_log2 = self.block_depth.trailing_zeros() as u8;
+        ((block_depth_log2 << 4) | block_height_log2, 0)
+    }
+
+    /// Block height of a mip, shrunk to fit small mips.
+    fn mip_block_height(&self, height_in_blocks: u32) -> BlockHeight {
+        tegra_swizzle::mip_block_height(height_in_blocks, self.block_height)
+    }
+
+    /// Size of a swizzled mip in bytes.
+    pub fn swizzled_size(
+        &self,
+        width_in_blocks: u32,
+        height_in_blocks: u32,
+        depth: u32,
+        bytes_per_block: u32,
+    ) -> usize {
+        swizzled_mip_size(
+            width_in_blocks,
+            height_in_blocks,
+            depth,
+            self.mip_block_height(height_in_blocks),
+            bytes_per_block,
+        )
+    }
+
+    /// Convert a swizzled mip to linear layout.
+    pub fn deswizzle(
+        &self,
+        data: &[u8],
+        width_in_blocks: u32,
+        height_in_blocks: u32,
+        depth: u32,
+        bytes_per_block: u32,
+    ) -> Result<Vec<u8>> {
+        Ok(deswizzle_block_linear(
+            width_in_blocks,
+            height_in_blocks,
+            depth,
+            data,
+            self.mip_block_height(height_in_blocks),
+            bytes_per_block,
+        )?)
+    }
+
+    /// Convert a linear mip to swizzled layout.
+    pub fn swizzle(
+        &self,
+        data: &[u8],
+        width_in_blocks: u32,
+        height_in_blocks: u32,
+        depth: u32,
+        bytes_per_block: u32,
+    ) -> Result<Vec<u8>> {
+        Ok(swizzle_block_linear(
+            width_in_blocks,
+            height_in_blocks,
+            depth,
+            data,
+            self.mip_block_height(height_in_blocks),
+            bytes_per_block,
+        )?)
+    }
+}
+
+/// Block depth in GOBs of a mip `depth` slices deep, as picked by the driver
+/// and `tegra_swizzle`.
+fn block_depth(depth: u32) -> u32 {
+    match depth + depth / 2 {
+        16.. => 16,
+        8.. => 8,
+        4.. => 4,
+        2.. => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Offset of byte `x` of row `y` in a 64x8 byte GOB, from the Tegra X1 TRM.
+    fn gob_offset(x: usize, y: usize) -> usize {
+        (x % 64) / 32 * 256 + (y % 8) / 2 * 64 + (x % 32) / 16 * 32 + (y % 2) * 16 + x % 16
+    }
+
+    #[test]
+    fn test_swizzle_reference() {
+        // 32x32 RGBA, 2 GOBs wide and 2 blocks of 2 GOBs tall
+        let (width, height, bytes_per_block) = (32, 32, 4);
+        let pitch = (width * bytes_per_block) as usize;
+        let linear: Vec<u8> = (0..pitch * height as usize)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect();
+
+        let block_height = 2;
+        let gobs_per_row = pitch.div_ceil(64);
+        let mut expected = vec![0; linear.len()];
+        for y in 0..height as usize {
+            for x in 0..pitch {
+                let gob_row = y / 8;
+                let block = gob_row / block_height * gobs_per_row + x / 64;
+                let gob = block * block_height + gob_row % block_height;
+                expected[gob * 512 + gob_offset(x, y)] = linear[y * pitch + x];
+            }
+        }
+
+        let block_linear = BlockLinear::new(1, 1).unwrap();
+        assert_eq!(
+            block_linear.swizzled_size(width, height, 1, bytes_per_block),
+            expected.len()
+        );
+        let swizzled = block_linear
+            .swizzle(&linear, width, height, 1, bytes_per_block)
+            .unwrap();
+        assert_eq!(swizzled, expected);
+        let deswizzled = block_linear
+            .deswizzle(&expected, width, height, 1, bytes_per_block)
+            .unwrap();
+        assert_eq!(deswizzled, linear);
+    }
+
+    #[test]
+    fn test_swizzle_header() {
+        let block_linear = BlockLinear::from_header(0x04, 0, 1).unwrap();
+        assert_eq!(block_linear.block_height, BlockHeight::Sixteen);
+        assert_eq!(block_linear.header_values(), (0x04, 0));
+
+        // volumes store the block depth in the high nibble
+        let block_linear = BlockLinear::new(4, 4).unwrap();
+        assert_eq!(block_linear.header_values(), (0x24, 0));
+        BlockLinear::from_header(0x24, 0, 4).unwrap();
+        assert!(BlockLinear::from_header(0x04, 0, 4).is_err());
+        assert!(BlockLinear::from_header(0x14, 0, 1).is_err());
+
+        assert!(BlockLinear::from_header(0x04, 1, 1).is_err());
+        assert!(BlockLinear::from_header(0x06, 0, 1).is_err());
+    }
+}
@@ -1,13 +1,185 @@
 use std::borrow::Cow;
-use std::io::{self, Write as _};
+use std::io::{self, Read as _, Write as _};
+use std::ops::Range;
 
 use byteorder::{LE, ReadBytesExt, WriteBytesExt};
-use ddsfile::{AlphaMode, D3D10ResourceDimension, Dds, DxgiFormat, NewDxgiParams};
-use num_traits::FromPrimitive;
+use ddsfile::{AlphaMode, Caps2, D3D10ResourceDimension, Dds, MiscFlag, NewDxgiParams};
 
+use crate::CompressionLevel;
 use crate::error::{Error, Result};
 use crate::format::TexFormat;
 use crate::gdf;
+use crate::gdf::TileSize;
+use crate::swizzle::BlockLinear;
+use crate::version::{TexLayout, TexVersion};
+
+/// Magic number of `.astc` files.
+const ASTC_MAGIC: u32 = 0x5CA1AB13;
+
+/// Options for [`Tex::from_rgba_image`].
+#[cfg(feature = "image")]
+#[derive(Debug, Clone)]
+pub struct ImageEncodeOptions {
+    /// Tex file version to create.
+    pub version: TexVersion,
+    /// Encoder quality for block compressed formats.
+    pub quality: image_dds::Quality,
+    /// How many mipmaps to generate.
+    pub mipmaps: image_dds::Mipmaps,
+}
+
+#[cfg(feature = "image")]
+impl ImageEncodeOptions {
+    /// Normal quality with a full mip chain.
+    pub fn new(version: TexVersion) -> Self {
+        Self {
+            version,
+            quality: image_dds::Quality::Normal,
+            mipmaps: image_dds::Mipmaps::GeneratedAutomatic,
+        }
+    }
+}
+
+/// Options for [`Tex::batch_compress_with`].
+#[derive(Debug, Clone)]
+pub struct CompressOptions {
+    /// GDeflate compression level.
+    pub level: CompressionLevel,
+    /// GDeflate tile size.
+    pub tile_size: TileSize,
+    /// Keep mipmaps uncompressed when GDeflate does not make them smaller.
+    pub only_if_smaller: bool,
+}
+
+impl CompressOptions {
+    /// Default 64 KiB tiles, compressing every mipmap.
+    pub fn new(level: CompressionLevel) -> Self {
+        Self {
+            level,
+            tile_size: TileSize::default(),
+            only_if_smaller: false,
+        }
+    }
+}
+
+/// Builder of a [`Tex`] from raw mip data.
+///
+/// Mip data is packed, without row padding or swizzling, and is stored
+/// uncompressed. Use [`Tex::batch_compress`] to GDeflate it afterwards.
+#[derive(Debug, Clone)]
+pub struct TexBuilder {
+    version: TexVersion,
+    format: TexFormat,
+    width: u16,
+    height: u16,
+    depth: u16,
+    cubemap: bool,
+    unknown_bytes: (u8, u8),
+    /// Mip data of each layer, from the largest mip.
+    layers: Vec<Vec<Vec<u8>>>,
+}
+
+impl TexBuilder {
+    pub fn new(version: TexVersion, format: TexFormat, width: u16, height: u16) -> Self {
+        Self {
+            version,
+            format,
+            width,
+            height,
+            depth: 1,
+            cubemap: false,
+            unknown_bytes: (0, 0),
+            layers: Vec::new(),
+        }
+    }
+
+    /// Depth of volume textures, 1 by default.
+    pub fn depth(mut self, depth: u16) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Store the layers as cubemap faces, 6 per cube.
+    pub fn cubemap(mut self, cubemap: bool) -> Self {
+        self.cubemap = cubemap;
+        self
+    }
+
+    /// Unknown header bytes at 0x1C and 0x1D, 0 by default.
+    ///
+    /// They vary per texture in shipped files, see [`TexHeader::unknown_bytes`].
+    pub fn unknown_bytes(mut self, unkn04: u8, unkn05: u8) -> Self {
+        self.unknown_bytes = (unkn04, unkn05);
+        self
+    }
+
+    /// Add a texture layer with the data of each mip, from the largest.
+    ///
+    /// Every layer must have the same mip count.
+    pub fn layer(mut self, mipmaps: Vec<Vec<u8>>) -> Self {
+        self.layers.push(mipmaps);
+        self
+    }
+
+    /// Build the Tex, checking the dimensions and mip data sizes.
+    pub fn build(&self) -> Result<Tex> {
+        let tex_count = u8::try_from(self.layers.len())
+            .ok()
+            .filter(|&count| count > 0)
+            .ok_or_else(|| {
+                Error::InvalidTexData(format!("Unsupported layer count: {}", self.layers.len()))
+            })?;
+        if self.cubemap && !tex_count.is_multiple_of(6) {
+            return Err(Error::InvalidTexData(format!(
+                "Cubemap layer count is not a multiple of 6: {tex_count}"
+            )));
+        }
+        let mipmap_count = self.layers[0].len();
+        if self.layers.iter().any(|mips| mips.len() != mipmap_count) {
+            return Err(Error::InvalidTexData(
+                "Layers have different mip counts".to_string(),
+            ));
+        }
+        let mipmap_count = u8::try_from(mipmap_count).map_err(|_| {
+            Error::UnsupportedByVersion(self.version.0, format!("{mipmap_count} mipmaps"))
+        })?;
+
+        let header = TexHeader {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            mipmap_count,
+            tex_count,
+            format: self.format,
+            cubemap_marker: if self.cubemap { 4 } else { 0 },
+            unkn04: self.unknown_bytes.0,
+            unkn05: self.unknown_bytes.1,
+            ..TexHeader::new(self.version)
+        };
+        check_layout_limits(&header)?;
+
+        for (layer, mips) in self.layers.iter().enumerate() {
+            for (level, mip) in mips.iter().enumerate() {
+                let MipLayout {
+                    pitch,
+                    rows,
+                    slices,
+                    ..
+                } = mip_layout(&header, level as u32)
+                    .ok_or(Error::UnsupportedTexFormat(self.format as u32))?;
+                let size = pitch as usize * rows as usize * slices as usize;
+                if mip.len() != size {
+                    return Err(Error::InvalidTexData(format!(
+                        "Layer {layer} mipmap {level} size mismatch: expected {size} bytes, got {}",
+                        mip.len()
+                    )));
+                }
+            }
+        }
+
+        Tex::from_packed_mips(header, self.layers.iter().flatten().map(Vec::as_slice))
+    }
+}
 
 #[derive(Debug, Clone, better_default::Default)]
 pub struct TexHeader {
@@ -21,6 +193,7 @@ pub struct TexHeader {
     pub mipmap_header_size: u8,
     #[default(TexFormat::Bc7Unorm)]
     pub format: TexFormat,
+    #[default(-1)]
     pub swizzle_control: i32,
     pub cubemap_marker: u32,
     unkn04: u8,
@@ -37,6 +210,18 @@ pub struct TexHeader {
 impl TexHeader {
     const MAGIC: [u8; 4] = [0x54, 0x45, 0x58, 0x00];
 
+    /// Empty header of `version`, with the magic filled in and other fields zero.
+    ///
+    /// Shipped files set the [unknown bytes](Self::unknown_bytes) per texture,
+    /// e.g. 0x00 0x05 and 0x80 0x01 in MH Wilds.
+    pub fn new(version: TexVersion) -> Self {
+        Self {
+            magic: Self::MAGIC,
+            version: version.0,
+            ..Default::default()
+        }
+    }
+
     pub fn from_reader<R>(reader: &mut R) -> Result<Self>
     where
         R: io::Read,
@@ -52,7 +237,8 @@ impl TexHeader {
         this.height = reader.read_u16::<LE>()?;
         this.depth = reader.read_u16::<LE>()?;
 
-        if this.version > 11 && this.version != 190820018 {
+        let layout = this.layout();
+        if layout.mipmap_header_size {
             this.tex_count = reader.read_u8()?;
             this.mipmap_header_size = reader.read_u8()?;
             this.mipmap_count = this.mipmap_header_size / MipEntry::SIZE as u8;
@@ -71,7 +257,7 @@ impl TexHeader {
         this.unkn05 = reader.read_u8()?;
         this.null0 = reader.read_u16::<LE>()?;
 
-        if this.version > 27 && this.version != 190820018 {
+        if layout.swizzle_block {
             // swizzle data
             this.swizzle_height_depth = reader.read_u8()?;
             this.swizzle_width = reader.read_u8()?;
@@ -80,12 +266,6 @@ impl TexHeader {
             this.one = reader.read_u16::<LE>()?;
         }
 
-        if this.swizzle_control == 1 {
-            return Err(Error::Unimplemented(
-                "Swizzle not implemented yet.".to_string(),
-            ));
-        }
-
         Ok(this)
     }
 
@@ -99,7 +279,8 @@ impl TexHeader {
         writer.write_u16::<LE>(self.height)?;
         writer.write_u16::<LE>(self.depth)?;
 
-        if self.version > 11 && self.version != 190820018 {
+        let layout = self.layout();
+        if layout.mipmap_header_size {
             writer.write_u8(self.tex_count)?;
             writer.write_u8(self.mipmap_header_size)?;
         } else {
@@ -114,7 +295,7 @@ impl TexHeader {
         writer.write_u8(self.unkn05)?;
         writer.write_u16::<LE>(self.null0)?;
 
-        if self.version > 27 && self.version != 190820018 {
+        if layout.swizzle_block {
             writer.write_u8(self.swizzle_height_depth)?;
             writer.write_u8(self.swizzle_width)?;
             writer.write_u16::<LE>(self.null1)?;
@@ -124,9 +305,62 @@ impl TexHeader {
 
         Ok(writer.into_inner())
     }
+
+    /// Whether mip data is stored in block linear (swizzled) layout.
+    pub fn is_swizzled(&self) -> bool {
+        self.swizzle_control == 1
+    }
+
+    /// Unknown header bytes at 0x1C and 0x1D.
+    ///
+    /// Pass them to [`TexBuilder::unknown_bytes`] when rebuilding a texture.
+    pub fn unknown_bytes(&self) -> (u8, u8) {
+        (self.unkn04, self.unkn05)
+    }
+
+    /// Block linear layout of swizzled data, from the swizzle header fields.
+    fn block_linear(&self) -> Result<BlockLinear> {
+        BlockLinear::from_header(
+            self.swizzle_height_depth,
+            self.swizzle_width,
+            self.depth as u32,
+        )
+    }
+
+    /// Get the Tex file version.
+    pub fn tex_version(&self) -> TexVersion {
+        TexVersion(self.version)
+    }
+
+    /// Get the layout rules of the Tex file version.
+    pub fn layout(&self) -> TexLayout {
+        self.tex_version().layout()
+    }
+
+    /// Whether the mip table is followed by a [`CompressionInfo`] table.
+    ///
+    /// Only MH Wilds stores it. Older versions locate mip data with
+    /// [`MipEntry`] offsets only.
+    pub fn has_compression_info(&self) -> bool {
+        self.layout().compression_info
+    }
+
+    /// Whether the header has the swizzle data block.
+    fn has_swizzle_block(&self) -> bool {
+        self.layout().swizzle_block
+    }
+
+    /// Size of the header in bytes.
+    fn size(&self) -> usize {
+        self.layout().header_size()
+    }
+
+    /// Row pitch alignment of mip data in bytes.
+    fn pitch_alignment(&self) -> u32 {
+        self.layout().pitch_alignment
+    }
 }
 
-#[repr(C)]
 #[derive(Debug, Clone)]
 pub struct MipEntry {
     offset: u64,
@@ -135,23 +369,49 @@ pub struct MipEntry {
 }
 
 impl MipEntry {
-    const SIZE: usize = size_of::<MipEntry>();
+    const SIZE: usize = 16;
 
     pub fn from_reader<R>(reader: &mut R) -> Result<Self>
     where
         R: io::Read,
     {
-        let mut buf = [0; Self::SIZE];
-        reader.read_exact(&mut buf)?;
-        unsafe { Ok(std::mem::transmute::<[u8; 16], MipEntry>(buf)) }
+        Ok(Self {
+            offset: reader.read_u64::<LE>()?,
+            scanline_length: reader.read_u32::<LE>()?,
+            uncompressed_size: reader.read_u32::<LE>()?,
+        })
     }
 
     pub fn as_bytes(&self) -> [u8; Self::SIZE] {
-        unsafe { std::mem::transmute::<MipEntry, [u8; 16]>(self.clone()) }
+        let mut buf = [0; Self::SIZE];
+        let mut writer = &mut buf[..];
+        // writing into a fixed size buffer can't fail
+        writer.write_u64::<LE>(self.offset).unwrap();
+        writer.write_u32::<LE>(self.scanline_length).unwrap();
+        writer.write_u32::<LE>(self.uncompressed_size).unwrap();
+        buf
+    }
+
+    /// Offset of the uncompressed mip data.
+    ///
+    /// Relative to the start of the file, without the [`CompressionInfo`] table.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Row pitch of the mip data in bytes, including padding.
+    pub fn scanline_length(&self) -> u32 {
+        self.scanline_length
+    }
+
+    /// Size of a single depth slice of the uncompressed mip data.
+    ///
+    /// For swizzled textures this is the size of the whole swizzled mip.
+    pub fn uncompressed_size(&self) -> u32 {
+        self.uncompressed_size
     }
 }
 
-#[repr(C)]
 #[derive(Debug, Clone)]
 pub struct CompressionInfo {
     compressed_size: u32,
@@ -159,19 +419,35 @@ pub struct CompressionInfo {
 }
 
 impl CompressionInfo {
-    const SIZE: usize = size_of::<CompressionInfo>();
+    const SIZE: usize = 8;
 
     pub fn from_reader<R>(reader: &mut R) -> Result<Self>
     where
         R: io::Read,
     {
-        let mut buf = [0; Self::SIZE];
-        reader.read_exact(&mut buf)?;
-        unsafe { Ok(std::mem::transmute::<[u8; 8], CompressionInfo>(buf)) }
+        Ok(Self {
+            compressed_size: reader.read_u32::<LE>()?,
+            compressed_offset: reader.read_u32::<LE>()?,
+        })
     }
 
     pub fn as_bytes(&self) -> [u8; Self::SIZE] {
-        unsafe { std::mem::transmute::<CompressionInfo, [u8; 8]>(self.clone()) }
+        let mut buf = [0; Self::SIZE];
+        let mut writer = &mut buf[..];
+        // writing into a fixed size buffer can't fail
+        writer.write_u32::<LE>(self.compressed_size).unwrap();
+        writer.write_u32::<LE>(self.compressed_offset).unwrap();
+        buf
+    }
+
+    /// Size of the stored mip data, compressed or not.
+    pub fn compressed_size(&self) -> u32 {
+        self.compressed_size
+    }
+
+    /// Offset of the stored mip data, relative to the end of the mip tables.
+    pub fn compressed_offset(&self) -> u32 {
+        self.compressed_offset
     }
 }
 
@@ -197,7 +473,7 @@ impl std::fmt::Debug for MipData {
 
 impl MipData {
     pub fn new(entry: MipEntry, compression_info: CompressionInfo, texture_data: Vec<u8>) -> Self {
-        let is_gdeflate = texture_data.len() >= 2 && texture_data[0..2] == [0x04, 0xFB];
+        let is_gdeflate = gdf::is_gdeflate(&texture_data);
         Self {
             entry,
             compression_info,
@@ -206,24 +482,37 @@ impl MipData {
         }
     }
 
+    /// Get the uncompressed data, decompressing GDeflate data.
+    ///
+    /// Without a `decompressor`, the one cached on the calling thread is used.
     pub fn uncompressed_data(
         &self,
         decompressor: Option<&mut gdf::GDfDecompressor>,
     ) -> Result<Cow<'_, Vec<u8>>> {
         if self.is_gdeflate {
-            // let mut decompressor = gdf::GDfDecompressor::new()?;
-            let decompressor = if let Some(decompressor) = decompressor {
-                decompressor
-            } else {
-                &mut gdf::GDfDecompressor::new()?
+            let out_data = match decompressor {
+                Some(decompressor) => decompressor.decompress(&self.texture_data)?,
+                None => gdf::with_decompressor(|decompressor| {
+                    decompressor.decompress(&self.texture_data)
+                })?,
             };
-            let out_data = decompressor.decompress(&self.texture_data)?;
             Ok(Cow::Owned(out_data))
         } else {
             Ok(Cow::Borrowed(&self.texture_data))
         }
     }
 
+    /// Get the uncompressed data size.
+    ///
+    /// For GDeflate data this is read from the stream header, without decompressing.
+    pub fn uncompressed_size(&self) -> Result<usize> {
+        if self.is_gdeflate {
+            Ok(gdf::uncompressed_size(&self.texture_data)?)
+        } else {
+            Ok(self.texture_data.len())
+        }
+    }
+
     pub fn is_compressed(&self) -> bool {
         self.is_gdeflate
     }
@@ -243,31 +532,14 @@ impl Tex {
         R: io::Read,
     {
         let header = TexHeader::from_reader(reader)?;
-
-        let num_mipmaps = header.mipmap_count as usize * header.tex_count as usize;
-        let mut mip_entries = Vec::with_capacity(num_mipmaps);
-        for _ in 0..num_mipmaps {
-            mip_entries.push(MipEntry::from_reader(reader)?);
-        }
-        let mut compression_infos = Vec::with_capacity(num_mipmaps);
-        for _ in 0..num_mipmaps {
-            compression_infos.push(CompressionInfo::from_reader(reader)?);
-        }
-
-        if mip_entries.len() != compression_infos.len() {
-            return Err(Error::Internal(
-                "mip_entries and compression_infos have different lengths".to_string(),
-            ));
-        }
+        let (mip_entries, compression_infos) = read_mip_tables(reader, &header)?;
 
         let mut data = Vec::new();
         reader.read_to_end(&mut data)?;
 
         // read mipmap data
-        let mut mipmaps = Vec::with_capacity((header.tex_count * header.mipmap_count) as usize);
-        for (idx, (mip_entry, compression_info)) in
-            mip_entries.iter().zip(compression_infos.iter()).enumerate()
-        {
+        let mut mipmaps = Vec::with_capacity(compression_infos.len());
+        for (idx, compression_info) in compression_infos.iter().enumerate() {
             let start = compression_info.compressed_offset as usize;
             let size = compression_info.compressed_size as usize;
             let end = start.checked_add(size).ok_or_else(|| {
@@ -275,12 +547,7 @@ impl Tex {
                     "Mipmap {idx} compressed range overflow: offset={start}, size={size}",
                 ))
             })?;
-            let padding = if header.swizzle_control != 1 {
-                0
-            } else {
-                mip_entry.uncompressed_size - mip_entry.scanline_length
-            };
-            let mut mip_data = data
+            let mip_data = data
                 .get(start..end)
                 .ok_or_else(|| {
                     Error::InvalidTexData(format!(
@@ -289,9 +556,6 @@ impl Tex {
                     ))
                 })?
                 .to_vec();
-            if padding > 0 {
-                mip_data.extend(vec![0u8; padding as usize]);
-            }
             mipmaps.push(mip_data);
         }
 
@@ -304,159 +568,1260 @@ impl Tex {
             .collect();
 
         // validate
-        let mut _idx = 0;
-        for _ in 0..header.tex_count {
-            for img_mip_idx in 0..header.mipmap_count {
-                let mip_data = &mip_datas[_idx];
-                if mip_data.uncompressed_data(None)?.len()
-                    != mip_data.entry.uncompressed_size as usize
-                        * u32::max(1, header.depth as u32 >> img_mip_idx) as usize
-                {
-                    return Err(Error::Internal(
-                        "Mipmap data size does not match mipmap entry size".to_string(),
-                    ));
-                }
-                _idx += 1;
-            }
+        if header.cubemap_marker != 0 && !header.tex_count.is_multiple_of(6) {
+            return Err(Error::InvalidTexData(format!(
+                "Cubemap texture count is not a multiple of 6: {}",
+                header.tex_count
+            )));
+        }
+        for (idx, mip_data) in mip_datas.iter().enumerate() {
+            let level = (idx % header.mipmap_count as usize) as u32;
+            check_mip_size(&header, level, mip_data)?;
         }
 
         Ok(Tex { header, mip_datas })
     }
 
     /// Create a new Tex file data.
+    ///
+    /// Same as [`Tex::write_to`], into a `Vec`.
     pub fn as_bytes(self) -> Result<Vec<u8>> {
-        let buf: Vec<u8> = Vec::new();
-        let mut writer = io::Cursor::new(buf);
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes)?;
+        Ok(bytes)
+    }
 
-        writer.write_all(&self.header.as_bytes()?)?;
+    /// Write the Tex file to `writer`.
+    ///
+    /// The mip tables are rebuilt from the mip data instead of trusting the
+    /// stored ones: `mipmap_header_size`, the mip entry offsets and the
+    /// compression infos are recomputed, and mipmaps are packed without gaps.
+    /// Mip entry sizes that do not match the mip data, or GDeflate data in
+    /// versions without compression infos, are rejected.
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        let (header, entries, compression_infos) = self.rebuild_mip_tables()?;
+        writer.write_all(&header.as_bytes()?)?;
         // write mipmap entries and compression infos
-        for mip_data in &self.mip_datas {
-            writer.write_all(&mip_data.entry.as_bytes())?;
+        for entry in &entries {
+            writer.write_all(&entry.as_bytes())?;
         }
-        for mip_data in &self.mip_datas {
-            writer.write_all(&mip_data.compression_info.as_bytes())?;
+        if header.has_compression_info() {
+            for compression_info in &compression_infos {
+                writer.write_all(&compression_info.as_bytes())?;
+            }
         }
         // write mipmap data
         for mip_data in &self.mip_datas {
             writer.write_all(&mip_data.texture_data)?;
         }
+        Ok(())
+    }
+
+    /// Rebuild `mipmap_header_size`, the mip entry offsets and the compression
+    /// infos from the mip data.
+    ///
+    /// The stored mip entry sizes are checked against the mip data, and
+    /// GDeflate data is rejected for versions without compression infos.
+    fn rebuild_mip_tables(&self) -> Result<(TexHeader, Vec<MipEntry>, Vec<CompressionInfo>)> {
+        let mut header = self.header.clone();
+        let num_mipmaps = header.mipmap_count as usize * header.tex_count as usize;
+        if self.mip_datas.len() != num_mipmaps {
+            return Err(Error::InvalidTexData(format!(
+                "Mipmap count mismatch: header={num_mipmaps}, mip_datas={}",
+                self.mip_datas.len()
+            )));
+        }
+        if !header.has_compression_info() && self.mip_datas.iter().any(MipData::is_compressed) {
+            return Err(Error::UnsupportedByVersion(
+                header.version,
+                "GDeflate compressed mipmaps".to_string(),
+            ));
+        }
+        header.mipmap_header_size = if header.layout().mipmap_header_size {
+            u8::try_from(header.mipmap_count as usize * MipEntry::SIZE).map_err(|_| {
+                Error::InvalidTexData(format!("Too many mipmaps: {}", header.mipmap_count))
+            })?
+        } else {
+            0
+        };
+
+        // entry offsets locate the uncompressed data, as if stored after the mip entries
+        let mut offset = (header.size() + num_mipmaps * MipEntry::SIZE) as u64;
+        let mut compressed_offset = 0u64;
+        let mut entries = Vec::with_capacity(num_mipmaps);
+        let mut compression_infos = Vec::with_capacity(num_mipmaps);
+        for (idx, mip_data) in self.mip_datas.iter().enumerate() {
+            let level = (idx % header.mipmap_count as usize) as u32;
+            check_mip_size(&header, level, mip_data)?;
+            let uncompressed_size = mip_data.uncompressed_size()? as u64;
+            if header.is_swizzled() && uncompressed_size != mip_data.entry.uncompressed_size as u64
+            {
+                return Err(Error::InvalidTexData(format!(
+                    "Mipmap {idx} entry size {} does not match its data size {uncompressed_size}",
+                    mip_data.entry.uncompressed_size
+                )));
+            }
+
+            let size_error = || Error::InvalidTexData(format!("Mipmap {idx} data exceeds 4 GiB"));
+            entries.push(MipEntry {
+                offset,
+                ..mip_data.entry.clone()
+            });
+            compression_infos.push(CompressionInfo {
+                compressed_size: u32::try_from(mip_data.texture_data.len())
+                    .map_err(|_| size_error())?,
+                compressed_offset: u32::try_from(compressed_offset).map_err(|_| size_error())?,
+            });
+            offset += uncompressed_size;
+            compressed_offset += mip_data.texture_data.len() as u64;
+        }
+        Ok((header, entries, compression_infos))
+    }
 
-        Ok(writer.into_inner())
+    /// Create a Tex from a DDS file.
+    ///
+    /// This is the inverse of [`Tex::to_dds`]. Mipmaps are stored uncompressed,
+    /// and checked against `version` the same way as [`TexBuilder::build`].
+    pub fn from_dds(dds: &Dds, version: TexVersion) -> Result<Self> {
+        let dxgi_format = dds.get_dxgi_format().ok_or(Error::UnsupportedDdsFormat)?;
+        let format = TexFormat::from_dxgi(dxgi_format)?;
+
+        let width = u16::try_from(dds.get_width())
+            .map_err(|_| Error::InvalidDdsData(format!("width too large: {}", dds.get_width())))?;
+        let height = u16::try_from(dds.get_height()).map_err(|_| {
+            Error::InvalidDdsData(format!("height too large: {}", dds.get_height()))
+        })?;
+        let depth = u16::try_from(dds.get_depth())
+            .map_err(|_| Error::InvalidDdsData(format!("depth too large: {}", dds.get_depth())))?;
+        // deeper levels would be empty
+        let max_levels = width
+            .max(height)
+            .max(depth)
+            .checked_ilog2()
+            .map_or(0, |log2| log2 + 1);
+        let mipmap_count = u8::try_from(dds.get_num_mipmap_levels())
+            .ok()
+            .filter(|&count| count > 0 && count as u32 <= max_levels)
+            .ok_or_else(|| {
+                Error::InvalidDdsData(format!(
+                    "unsupported mipmap count: {}",
+                    dds.get_num_mipmap_levels()
+                ))
+            })?;
+
+        let is_cubemap = match &dds.header10 {
+            Some(header10) => header10.misc_flag.contains(MiscFlag::TEXTURECUBE),
+            None => dds.header.caps2.contains(Caps2::CUBEMAP),
+        };
+        // partial cubemaps have no Tex equivalent
+        if dds.header10.is_none()
+            && is_cubemap
+            && !dds.header.caps2.contains(Caps2::CUBEMAP_ALLFACES)
+        {
+            return Err(Error::InvalidDdsData(
+                "cubemap is missing faces".to_string(),
+            ));
+        }
+        // ddsfile counts cubes instead of faces for DX10 cubemaps.
+        let num_layers = match &dds.header10 {
+            Some(header10) if is_cubemap => header10.array_size * 6,
+            _ => dds.get_num_array_layers(),
+        };
+        let tex_count = u8::try_from(num_layers)
+            .map_err(|_| Error::InvalidDdsData(format!("too many array layers: {num_layers}")))?;
+
+        let mut builder = TexBuilder::new(version, format, width, height)
+            .depth(depth)
+            .cubemap(is_cubemap);
+        let mut data = dds.data.as_slice();
+        for _ in 0..tex_count {
+            let mut mips = Vec::with_capacity(mipmap_count as usize);
+            for level in 0..mipmap_count as u32 {
+                let size = format
+                    .mip_size(width as u32, height as u32, depth as u32, level)
+                    .ok_or(Error::UnsupportedDdsFormat)?;
+                if data.len() < size {
+                    return Err(Error::InvalidDdsData(format!(
+                        "data too short for mipmap {level}: expected {size} bytes, got {}",
+                        data.len()
+                    )));
+                }
+                let (mip, rest) = data.split_at(size);
+                data = rest;
+                mips.push(mip.to_vec());
+            }
+            builder = builder.layer(mips);
+        }
+
+        builder.build()
+    }
+
+    /// Create a Tex from packed mip data stored layer by layer, padding rows
+    /// to the pitch alignment of the header version.
+    fn from_packed_mips<'a>(
+        header: TexHeader,
+        mips: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Result<Self> {
+        let pitch_alignment = header.pitch_alignment();
+        let mut mip_datas = Vec::new();
+        for (idx, mip) in mips.into_iter().enumerate() {
+            let level = (idx % header.mipmap_count as usize) as u32;
+            let MipLayout { pitch, rows, .. } = mip_layout(&header, level)
+                .ok_or(Error::UnsupportedTexFormat(header.format as u32))?;
+            let scanline_length = pitch.next_multiple_of(pitch_alignment);
+            let texture_data = pad_rows(mip, pitch, scanline_length);
+            let entry = MipEntry {
+                offset: 0,
+                scanline_length,
+                uncompressed_size: scanline_length * rows,
+            };
+            let compression_info = CompressionInfo {
+                compressed_size: 0,
+                compressed_offset: 0,
+            };
+            mip_datas.push(MipData::new(entry, compression_info, texture_data));
+        }
+
+        let mut tex = Tex { header, mip_datas };
+        tex.fix_offsets()?;
+        Ok(tex)
     }
 
     /// Decompress all mipmaps.
+    ///
+    /// With the `rayon` feature, the tiles of each mipmap are decompressed in parallel.
     pub fn batch_decompress(&mut self) -> Result<()> {
-        let mut decompressor = gdf::GDfDecompressor::new()?;
-
-        let mut curr_comp_offset = 0;
-        for mip_data in &mut self.mip_datas {
-            if !mip_data.is_compressed() {
+        let mip_datas = &mut self.mip_datas;
+        gdf::with_decompressor(|decompressor| {
+            let mut curr_comp_offset = 0;
+            for mip_data in mip_datas {
+                if !mip_data.is_compressed() {
+                    // fix header
+                    mip_data.compression_info.compressed_offset = curr_comp_offset;
+                    curr_comp_offset += mip_data.compression_info.compressed_size;
+                    continue;
+                }
+                #[cfg(feature = "rayon")]
+                let out_data = decompressor.par_decompress(&mip_data.texture_data)?;
+                #[cfg(not(feature = "rayon"))]
+                let out_data = decompressor.decompress(&mip_data.texture_data)?;
                 // fix header
                 mip_data.compression_info.compressed_offset = curr_comp_offset;
-                curr_comp_offset += mip_data.compression_info.compressed_size;
-                continue;
+                mip_data.compression_info.compressed_size = out_data.len() as u32;
+                mip_data.is_gdeflate = false;
+                curr_comp_offset += out_data.len() as u32;
+
+                mip_data.texture_data = out_data;
             }
-            let out_data = decompressor.decompress(&mip_data.texture_data)?;
-            // fix header
-            mip_data.compression_info.compressed_offset = curr_comp_offset;
-            mip_data.compression_info.compressed_size = out_data.len() as u32;
-            mip_data.is_gdeflate = false;
-            curr_comp_offset += out_data.len() as u32;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// GDeflate compress all mipmaps.
+    ///
+    /// Already compressed mipmaps are kept as is.
+    /// With the `rayon` feature, the tiles of each mipmap are compressed in parallel.
+    pub fn batch_compress(&mut self, level: CompressionLevel) -> Result<()> {
+        self.batch_compress_with(&CompressOptions::new(level))
+    }
 
-            mip_data.texture_data = out_data;
+    /// GDeflate compress all mipmaps with `options`.
+    ///
+    /// Already compressed mipmaps are kept as is.
+    pub fn batch_compress_with(&mut self, options: &CompressOptions) -> Result<()> {
+        if !self.header.has_compression_info() {
+            return Err(Error::UnsupportedByVersion(
+                self.header.version,
+                "GDeflate compression".to_string(),
+            ));
         }
+
+        let mip_datas = &mut self.mip_datas;
+        gdf::with_compressor(options.level, |compressor| {
+            compressor.set_tile_size(options.tile_size);
+            for mip_data in mip_datas {
+                if mip_data.is_compressed() || mip_data.texture_data.is_empty() {
+                    continue;
+                }
+                #[cfg(feature = "rayon")]
+                let compressed = compressor.par_compress(&mip_data.texture_data)?;
+                #[cfg(not(feature = "rayon"))]
+                let compressed = compressor.compress(&mip_data.texture_data)?;
+                if options.only_if_smaller && compressed.len() >= mip_data.texture_data.len() {
+                    continue;
+                }
+                mip_data.texture_data = compressed;
+                mip_data.is_gdeflate = true;
+            }
+            Ok(())
+        })?;
+        // fix header
+        self.fix_offsets()?;
         Ok(())
     }
 
-    /// Convert to DDS file.
+    /// Convert swizzled mip data to linear layout.
     ///
-    /// `mipmap_count` is the number of mipmaps to convert.
-    pub fn to_dds(&self, mipmap_count: usize) -> Result<Dds> {
-        if mipmap_count > self.mip_datas.len() {
-            return Err(Error::Internal("mipmap_count is out of range".to_string()));
+    /// Does nothing if the Tex is not swizzled.
+    pub fn deswizzle(&mut self) -> Result<()> {
+        if !self.header.is_swizzled() {
+            return Ok(());
         }
 
-        // TODO: swizzle
-        let mipmaps = &self.mip_datas[0..mipmap_count];
+        let pitch_alignment = self.header.pitch_alignment();
+        for idx in 0..self.mip_datas.len() {
+            let level = (idx % self.header.mipmap_count as usize) as u32;
+            let layout = mip_layout(&self.header, level)
+                .ok_or(Error::UnsupportedTexFormat(self.header.format as u32))?;
+            let data = self.linear_mip_data(idx)?;
 
-        let mut dds = Dds::new_dxgi(NewDxgiParams {
-            height: self.header.height as u32,
-            width: self.header.width as u32,
-            depth: Some(self.header.depth as u32),
-            format: DxgiFormat::from_u32(self.header.format as u32).unwrap(),
-            mipmap_levels: Some(mipmap_count as u32),
-            array_layers: None,
-            caps2: None,
-            is_cubemap: self.header.cubemap_marker != 0,
-            resource_dimension: D3D10ResourceDimension::Texture2D,
-            alpha_mode: AlphaMode::Unknown,
-        })?;
+            let scanline_length = layout.pitch.next_multiple_of(pitch_alignment);
+            let mip_data = &mut self.mip_datas[idx];
+            mip_data.texture_data = pad_rows(&data, layout.pitch, scanline_length);
+            mip_data.is_gdeflate = false;
+            mip_data.entry.scanline_length = scanline_length;
+            mip_data.entry.uncompressed_size = scanline_length * layout.rows;
+        }
 
-        // decompress if needed
-        let has_compressed = mipmaps.iter().any(|mip_data| mip_data.is_compressed());
-        let mut decompressor = if has_compressed {
-            Some(gdf::GDfDecompressor::new()?)
-        } else {
-            None
+        self.header.swizzle_control = -1;
+        self.header.swizzle_height_depth = 0;
+        self.header.swizzle_width = 0;
+        self.fix_offsets()?;
+        Ok(())
+    }
+
+    /// Convert linear mip data to block linear (swizzled) layout.
+    ///
+    /// `block_height_log2` is the log2 of the block height of the first mip, in GOBs.
+    pub fn swizzle(&mut self, block_height_log2: u8) -> Result<()> {
+        if !self.header.has_swizzle_block() {
+            return Err(Error::UnsupportedByVersion(
+                self.header.version,
+                "swizzling".to_string(),
+            ));
+        }
+        self.deswizzle()?;
+
+        let block_linear = BlockLinear::new(block_height_log2, self.header.depth as u32)?;
+        for idx in 0..self.mip_datas.len() {
+            let level = (idx % self.header.mipmap_count as usize) as u32;
+            let layout = mip_layout(&self.header, level)
+                .ok_or(Error::UnsupportedTexFormat(self.header.format as u32))?;
+            let data = self.linear_mip_data(idx)?;
+            let data = block_linear.swizzle(
+                &data,
+                layout.width_in_blocks(),
+                layout.rows,
+                layout.slices,
+                layout.bytes_per_block,
+            )?;
+
+            let mip_data = &mut self.mip_datas[idx];
+            mip_data.entry.scanline_length = layout.pitch;
+            mip_data.entry.uncompressed_size = data.len() as u32;
+            mip_data.texture_data = data;
+            mip_data.is_gdeflate = false;
+        }
+
+        self.header.swizzle_control = 1;
+        (self.header.swizzle_height_depth, self.header.swizzle_width) =
+            block_linear.header_values();
+        self.fix_offsets()?;
+        Ok(())
+    }
+
+    /// Convert to another Tex file version.
+    ///
+    /// The header is rewritten into the target layout, rows are padded to the
+    /// target pitch alignment, and mipmaps are GDeflate compressed only if the
+    /// target stores compression info, with `options`. Swizzled data is kept if
+    /// the target has the swizzle block.
+    ///
+    /// Fails if the target cannot store the format, mip count or mip sizes.
+    pub fn convert_to_version(&self, target: TexVersion, options: &CompressOptions) -> Result<Tex> {
+        let layout = target.layout();
+        let mut target_header = TexHeader {
+            version: target.0,
+            ..self.header.clone()
         };
+        if !layout.swizzle_block {
+            target_header.swizzle_control = -1;
+        }
+        check_layout_limits(&target_header)?;
 
-        let mut data: Vec<u8> = Vec::new();
-        for mip_data in mipmaps {
-            if mip_data.is_compressed() {
-                data.extend(mip_data.uncompressed_data(decompressor.as_mut())?.as_ref());
-            } else {
-                data.extend(mip_data.texture_data.as_slice());
+        let mut tex = self.clone();
+        if tex.header.is_swizzled() && !layout.swizzle_block {
+            tex.deswizzle()?;
+        }
+        tex.batch_decompress()?;
+
+        // pad rows to the target alignment
+        if !tex.header.is_swizzled() {
+            for idx in 0..tex.mip_datas.len() {
+                let level = (idx % tex.header.mipmap_count as usize) as u32;
+                let layout_mip = mip_layout(&tex.header, level)
+                    .ok_or(Error::UnsupportedTexFormat(tex.header.format as u32))?;
+                let data = tex.linear_mip_data(idx)?;
+
+                let scanline_length = layout_mip.pitch.next_multiple_of(layout.pitch_alignment);
+                let mip_data = &mut tex.mip_datas[idx];
+                mip_data.texture_data = pad_rows(&data, layout_mip.pitch, scanline_length);
+                mip_data.entry.scanline_length = scanline_length;
+                mip_data.entry.uncompressed_size = scanline_length * layout_mip.rows;
             }
         }
-        dds.data = data;
 
-        Ok(dds)
-    }
+        let header = &mut tex.header;
+        header.version = target.0;
+        header.mipmap_header_size = if layout.mipmap_header_size {
+            header.mipmap_count * MipEntry::SIZE as u8
+        } else {
+            0
+        };
+        if !layout.swizzle_block {
+            header.swizzle_height_depth = 0;
+            header.swizzle_width = 0;
+            header.null1 = 0;
+            header.seven = 0;
+            header.one = 0;
+        }
+        tex.fix_offsets()?;
 
-    /// Convert to Image struct.
-    #[cfg(feature = "image")]
-    pub fn to_rgba_image(&self, mipmap_idx: usize) -> Result<image::RgbaImage> {
-        if mipmap_idx >= self.mip_datas.len() {
-            return Err(Error::Internal("mipmap_idx is out of range".to_string()));
+        if layout.compression_info {
+            tex.batch_compress_with(options)?;
         }
 
-        let dds = self.to_dds(mipmap_idx + 1)?;
-        let rgba_image = image_dds::image_from_dds(&dds, mipmap_idx as u32)?;
+        Ok(tex)
+    }
 
-        Ok(rgba_image)
+    /// Recompute the mip tables from the mip data, see [`Tex::rebuild_mip_tables`].
+    fn fix_offsets(&mut self) -> Result<()> {
+        let (header, entries, compression_infos) = self.rebuild_mip_tables()?;
+        self.header = header;
+        for ((mip_data, entry), compression_info) in self
+            .mip_datas
+            .iter_mut()
+            .zip(entries)
+            .zip(compression_infos)
+        {
+            mip_data.entry = entry;
+            mip_data.compression_info = compression_info;
+        }
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Get the packed, linear data of a mip, without row padding or swizzling.
+    fn linear_mip_data(&self, idx: usize) -> Result<Vec<u8>> {
+        let mip_data = &self.mip_datas[idx];
+        let level = (idx % self.header.mipmap_count as usize) as u32;
+        let layout = mip_layout(&self.header, level)
+            .ok_or(Error::UnsupportedTexFormat(self.header.format as u32))?;
+        let data = mip_data.uncompressed_data(None)?;
+
+        if self.header.is_swizzled() {
+            let block_linear = self.header.block_linear()?;
+            return block_linear.deswizzle(
+                &data,
+                layout.width_in_blocks(),
+                layout.rows,
+                layout.slices,
+                layout.bytes_per_block,
+            );
+        }
 
-    const TEST_FILE_GDF: &str = "test_files/ch04_000_0000_1002_MB.tex.241106027";
-    const TEST_FILE_NO_GDF: &str = "test_files/uncompress_ch04_000_0000_1001_ALBD.tex.241106027";
-    const TEST_FILE_LEGACY: &str = "test_files/s_menu_iam.tex.34";
+        Ok(strip_row_padding(&data, mip_data.entry.scanline_length, layout.pitch).into_owned())
+    }
 
-    #[test]
-    fn test_parse_tex() {
-        let data = std::fs::read(TEST_FILE_NO_GDF).unwrap();
-        let mut reader = std::io::Cursor::new(data);
-        let tex = Tex::from_reader(&mut reader).unwrap();
-        eprintln!("{:#?}", tex);
+    /// Whether the texture is a cubemap or cubemap array.
+    ///
+    /// Every cube takes 6 texture layers, with faces in the same order as DDS:
+    /// +X, -X, +Y, -Y, +Z, -Z.
+    pub fn is_cubemap(&self) -> bool {
+        self.header.cubemap_marker != 0
     }
 
-    #[test]
-    fn test_parse_tex_gdf() {
-        let data = std::fs::read(TEST_FILE_GDF).unwrap();
-        let mut reader = std::io::Cursor::new(data);
-        let tex = Tex::from_reader(&mut reader).unwrap();
-        eprintln!("{:#?}", tex);
+    /// Whether the texture is a volume (3D) texture.
+    pub fn is_volume(&self) -> bool {
+        self.header.depth > 1
     }
 
+    /// Convert to DDS file.
+    ///
+    /// `mipmap_count` is the number of mipmaps to convert. All texture layers are
+    /// exported, as a texture array if there is more than one. Cubemaps are
+    /// exported as DDS cubemaps, volume textures as 3D textures.
+    pub fn to_dds(&self, mipmap_count: usize) -> Result<Dds> {
+        self.to_dds_layers(0..self.header.tex_count as usize, mipmap_count)
+    }
+
+    /// Convert a single texture layer to DDS file.
+    ///
+    /// `mipmap_count` is the number of mipmaps to convert.
+    pub fn to_dds_layer(&self, layer: usize, mipmap_count: usize) -> Result<Dds> {
+        self.to_dds_layers(layer..layer + 1, mipmap_count)
+    }
+
+    fn to_dds_layers(&self, layers: Range<usize>, mipmap_count: usize) -> Result<Dds> {
+        if mipmap_count == 0 || mipmap_count > self.header.mipmap_count as usize {
+            return Err(Error::Internal("mipmap_count is out of range".to_string()));
+        }
+        if layers.is_empty() || layers.end > self.header.tex_count as usize {
+            return Err(Error::Internal("layer is out of range".to_string()));
+        }
+
+        let num_layers = layers.len() as u32;
+        let is_cubemap = self.is_cubemap() && num_layers.is_multiple_of(6);
+        let is_volume = self.is_volume();
+        if is_volume && num_layers > 1 {
+            return Err(Error::Unimplemented(
+                "DDS does not support volume texture arrays".to_string(),
+            ));
+        }
+        let caps2 = if is_cubemap {
+            Some(Caps2::CUBEMAP | Caps2::CUBEMAP_ALLFACES)
+        } else if is_volume {
+            Some(Caps2::VOLUME)
+        } else {
+            None
+        };
+        let resource_dimension = if is_volume {
+            D3D10ResourceDimension::Texture3D
+        } else {
+            D3D10ResourceDimension::Texture2D
+        };
+        let format = self.header.format.to_dxgi()?;
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            height: self.header.height as u32,
+            width: self.header.width as u32,
+            depth: Some(self.header.depth as u32),
+            format,
+            mipmap_levels: Some(mipmap_count as u32),
+            array_layers: (num_layers > 1).then_some(num_layers),
+            caps2,
+            is_cubemap,
+            resource_dimension,
+            alpha_mode: AlphaMode::Unknown,
+        })?;
+
+        // mip_datas are stored layer by layer, same as DDS
+        let mipmap_indices = layers.flat_map(|layer| {
+            let start = layer * self.header.mipmap_count as usize;
+            start..start + mipmap_count
+        });
+
+        let mut data: Vec<u8> = Vec::new();
+        for idx in mipmap_indices {
+            data.extend_from_slice(&self.linear_mip_data(idx)?);
+        }
+        dds.data = data;
+
+        Ok(dds)
+    }
+
+    /// Convert to Image struct.
+    ///
+    /// Only the first texture layer is converted. Depth slices of volume
+    /// textures are arranged vertically from top to bottom.
+    ///
+    /// ASTC textures are decoded in software.
+    #[cfg(feature = "image")]
+    pub fn to_rgba_image(&self, mipmap_idx: usize) -> Result<image::RgbaImage> {
+        self.layer_rgba_image(0, mipmap_idx)
+    }
+
+    /// Convert a single depth slice of a volume texture to Image struct.
+    ///
+    /// `depth_idx` is the slice index within the mipmap, which has
+    /// `max(depth >> mipmap_idx, 1)` slices.
+    #[cfg(feature = "image")]
+    pub fn to_rgba_image_slice(
+        &self,
+        depth_idx: usize,
+        mipmap_idx: usize,
+    ) -> Result<image::RgbaImage> {
+        if mipmap_idx >= self.header.mipmap_count as usize {
+            return Err(Error::Internal("mipmap_idx is out of range".to_string()));
+        }
+        let layout = mip_layout(&self.header, mipmap_idx as u32)
+            .ok_or(Error::UnsupportedTexFormat(self.header.format as u32))?;
+        if depth_idx >= layout.slices as usize {
+            return Err(Error::Internal("depth_idx is out of range".to_string()));
+        }
+
+        let data = self.linear_mip_data(mipmap_idx)?;
+        let slice_size = (layout.pitch * layout.rows) as usize;
+        self.decode_slice(
+            &data[depth_idx * slice_size..][..slice_size],
+            mipmap_idx as u32,
+        )
+    }
+
+    /// Convert the six faces of a cube to Image structs.
+    ///
+    /// `cube_idx` is the index of the cube in a cubemap array. Faces are
+    /// returned in DDS order: +X, -X, +Y, -Y, +Z, -Z.
+    #[cfg(feature = "image")]
+    pub fn to_rgba_cubemap_faces(
+        &self,
+        cube_idx: usize,
+        mipmap_idx: usize,
+    ) -> Result<[image::RgbaImage; 6]> {
+        if !self.is_cubemap() {
+            return Err(Error::Internal("texture is not a cubemap".to_string()));
+        }
+        if cube_idx >= self.header.tex_count as usize / 6 {
+            return Err(Error::Internal("cube_idx is out of range".to_string()));
+        }
+
+        let mut faces = Vec::with_capacity(6);
+        for face in 0..6 {
+            faces.push(self.layer_rgba_image(cube_idx * 6 + face, mipmap_idx)?);
+        }
+
+        Ok(faces.try_into().unwrap())
+    }
+
+    /// Decode a mipmap of a texture layer, with depth slices arranged vertically.
+    #[cfg(feature = "image")]
+    fn layer_rgba_image(&self, layer: usize, mipmap_idx: usize) -> Result<image::RgbaImage> {
+        let mipmap_count = self.header.mipmap_count as usize;
+        if mipmap_idx >= mipmap_count {
+            return Err(Error::Internal("mipmap_idx is out of range".to_string()));
+        }
+        if layer >= self.header.tex_count as usize {
+            return Err(Error::Internal("layer is out of range".to_string()));
+        }
+        let layout = mip_layout(&self.header, mipmap_idx as u32)
+            .ok_or(Error::UnsupportedTexFormat(self.header.format as u32))?;
+
+        let data = self.linear_mip_data(layer * mipmap_count + mipmap_idx)?;
+        let slice_size = (layout.pitch * layout.rows) as usize;
+        let mut pixels = Vec::new();
+        for slice in data.chunks_exact(slice_size).take(layout.slices as usize) {
+            pixels.extend(self.decode_slice(slice, mipmap_idx as u32)?.into_raw());
+        }
+
+        let width = u32::max(1, self.header.width as u32 >> mipmap_idx);
+        let height = u32::max(1, self.header.height as u32 >> mipmap_idx) * layout.slices;
+        image::RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| Error::InvalidTexData("Mipmap data is too short".to_string()))
+    }
+
+    /// Decode a single depth slice of linear mip data.
+    #[cfg(feature = "image")]
+    fn decode_slice(&self, data: &[u8], level: u32) -> Result<image::RgbaImage> {
+        let width = u32::max(1, self.header.width as u32 >> level);
+        let height = u32::max(1, self.header.height as u32 >> level);
+
+        if self.header.format.is_astc() {
+            let (block_width, block_height) = self.header.format.block_dimensions();
+            let footprint = astc_decode::Footprint::new(block_width, block_height);
+            let mut image = image::RgbaImage::new(width, height);
+            astc_decode::astc_decode(data, width, height, footprint, |x, y, color| {
+                image.put_pixel(x, y, image::Rgba(color));
+            })?;
+            return Ok(image);
+        }
+
+        // decode as a 2D texture, image_dds only handles
+        // the first mip of volume textures
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            height,
+            width,
+            depth: None,
+            format: self.header.format.to_dxgi()?,
+            mipmap_levels: None,
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })?;
+        dds.data = data.to_vec();
+
+        Ok(image_dds::image_from_dds(&dds, 0)?)
+    }
+
+    /// Convert a mipmap of an ASTC texture to an `.astc` file.
+    ///
+    /// Depth slices of volume textures are stored as a 3D image of 2D blocks.
+    pub fn to_astc(&self, layer: usize, mipmap_idx: usize) -> Result<Vec<u8>> {
+        let format = self.header.format;
+        if !format.is_astc() {
+            return Err(Error::UnsupportedTexFormat(format as u32));
+        }
+        let mipmap_count = self.header.mipmap_count as usize;
+        if mipmap_idx >= mipmap_count {
+            return Err(Error::Internal("mipmap_idx is out of range".to_string()));
+        }
+        if layer >= self.header.tex_count as usize {
+            return Err(Error::Internal("layer is out of range".to_string()));
+        }
+        let layout = mip_layout(&self.header, mipmap_idx as u32)
+            .ok_or(Error::UnsupportedTexFormat(format as u32))?;
+        let data = self.linear_mip_data(layer * mipmap_count + mipmap_idx)?;
+
+        let (block_width, block_height) = format.block_dimensions();
+        let mut writer = io::Cursor::new(Vec::with_capacity(16 + data.len()));
+        writer.write_u32::<LE>(ASTC_MAGIC)?;
+        writer.write_all(&[block_width as u8, block_height as u8, 1])?;
+        writer.write_u24::<LE>(u32::max(1, self.header.width as u32 >> mipmap_idx))?;
+        writer.write_u24::<LE>(u32::max(1, self.header.height as u32 >> mipmap_idx))?;
+        writer.write_u24::<LE>(layout.slices)?;
+        writer.write_all(&data)?;
+
+        Ok(writer.into_inner())
+    }
+
+    /// Create a Tex from an Image struct.
+    ///
+    /// The image is encoded to `format` and the mip chain is generated
+    /// according to `options`.
+    #[cfg(feature = "image")]
+    pub fn from_rgba_image(
+        image: &image::RgbaImage,
+        format: TexFormat,
+        options: &ImageEncodeOptions,
+    ) -> Result<Self> {
+        let image_format = format
+            .to_image_format()
+            .ok_or(Error::UnsupportedTexFormat(format as u32))?;
+        let dds = image_dds::dds_from_image(image, image_format, options.quality, options.mipmaps)?;
+
+        let mut tex = Self::from_dds(&dds, options.version)?;
+        // keep typeless and other aliased formats as requested
+        tex.header.format = format;
+
+        Ok(tex)
+    }
+}
+
+/// Tex reader that loads mip data on demand.
+///
+/// Only the header and mip tables are parsed up front, mipmaps are read with
+/// [`TexReader::read_mip`] by seeking to their compressed data.
+pub struct TexReader<R> {
+    reader: R,
+    header: TexHeader,
+    mip_entries: Vec<MipEntry>,
+    compression_infos: Vec<CompressionInfo>,
+    /// Stream position of the first mip data.
+    data_start: u64,
+}
+
+impl<R> TexReader<R>
+where
+    R: io::Read + io::Seek,
+{
+    /// Parse the header and mip tables at the current stream position.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let header = TexHeader::from_reader(&mut reader)?;
+        let (mip_entries, compression_infos) = read_mip_tables(&mut reader, &header)?;
+        let data_start = reader.stream_position()?;
+
+        Ok(Self {
+            reader,
+            header,
+            mip_entries,
+            compression_infos,
+            data_start,
+        })
+    }
+
+    pub fn header(&self) -> &TexHeader {
+        &self.header
+    }
+
+    pub fn mip_entries(&self) -> &[MipEntry] {
+        &self.mip_entries
+    }
+
+    pub fn compression_infos(&self) -> &[CompressionInfo] {
+        &self.compression_infos
+    }
+
+    /// Read a single mipmap of a texture layer.
+    pub fn read_mip(&mut self, layer: usize, level: usize) -> Result<MipData> {
+        let mipmap_count = self.header.mipmap_count as usize;
+        if layer >= self.header.tex_count as usize || level >= mipmap_count {
+            return Err(Error::Internal(format!(
+                "mipmap {level} of layer {layer} is out of range"
+            )));
+        }
+        let idx = layer * mipmap_count + level;
+        let entry = self.mip_entries[idx].clone();
+        let compression_info = self.compression_infos[idx].clone();
+
+        self.reader.seek(io::SeekFrom::Start(
+            self.data_start + compression_info.compressed_offset as u64,
+        ))?;
+        // the size comes from the file, so grow the buffer as data arrives
+        let size = compression_info.compressed_size as usize;
+        let mut data = Vec::new();
+        (&mut self.reader)
+            .take(size as u64)
+            .read_to_end(&mut data)?;
+        if data.len() != size {
+            return Err(Error::InvalidTexData(format!(
+                "Mipmap {idx} compressed range out of bounds: offset={}, size={size}, data_len={}",
+                compression_info.compressed_offset,
+                data.len(),
+            )));
+        }
+
+        let mip_data = MipData::new(entry, compression_info, data);
+        check_mip_size(&self.header, level as u32, &mip_data)?;
+        Ok(mip_data)
+    }
+
+    /// Read all mipmaps into a [`Tex`].
+    pub fn read_tex(&mut self) -> Result<Tex> {
+        let mut mip_datas = Vec::with_capacity(self.mip_entries.len());
+        for layer in 0..self.header.tex_count as usize {
+            for level in 0..self.header.mipmap_count as usize {
+                mip_datas.push(self.read_mip(layer, level)?);
+            }
+        }
+        Ok(Tex {
+            header: self.header.clone(),
+            mip_datas,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Read the mip entry table and the compression info table.
+///
+/// Compression infos are derived from mip entries for versions without the table.
+fn read_mip_tables<R>(
+    reader: &mut R,
+    header: &TexHeader,
+) -> Result<(Vec<MipEntry>, Vec<CompressionInfo>)>
+where
+    R: io::Read,
+{
+    let num_mipmaps = header.mipmap_count as usize * header.tex_count as usize;
+    let mut mip_entries = Vec::with_capacity(num_mipmaps);
+    for _ in 0..num_mipmaps {
+        mip_entries.push(MipEntry::from_reader(reader)?);
+    }
+    let mut compression_infos = Vec::with_capacity(num_mipmaps);
+    if header.has_compression_info() {
+        for _ in 0..num_mipmaps {
+            compression_infos.push(CompressionInfo::from_reader(reader)?);
+        }
+    } else {
+        // legacy versions store absolute offsets of uncompressed data
+        let data_start = (header.size() + num_mipmaps * MipEntry::SIZE) as u64;
+        for (idx, mip_entry) in mip_entries.iter().enumerate() {
+            let level = idx % header.mipmap_count as usize;
+            let slices = if header.is_swizzled() {
+                1
+            } else {
+                u32::max(1, header.depth as u32 >> level)
+            };
+            let compressed_offset = mip_entry
+                .offset
+                .checked_sub(data_start)
+                .and_then(|offset| u32::try_from(offset).ok())
+                .ok_or_else(|| {
+                    Error::InvalidTexData(format!(
+                        "Mipmap {idx} offset out of range: offset={}",
+                        mip_entry.offset
+                    ))
+                })?;
+            let compressed_size = mip_entry
+                .uncompressed_size
+                .checked_mul(slices)
+                .ok_or_else(|| Error::InvalidTexData(format!("Mipmap {idx} size overflow")))?;
+            compression_infos.push(CompressionInfo {
+                compressed_size,
+                compressed_offset,
+            });
+        }
+    }
+
+    if mip_entries.len() != compression_infos.len() {
+        return Err(Error::Internal(
+            "mip_entries and compression_infos have different lengths".to_string(),
+        ));
+    }
+
+    Ok((mip_entries, compression_infos))
+}
+
+/// Check the data size of a mip against its mip entry.
+fn check_mip_size(header: &TexHeader, level: u32, mip_data: &MipData) -> Result<()> {
+    let data_len = mip_data.uncompressed_size()?;
+    let layout = mip_layout(header, level);
+    let size_ok = if header.is_swizzled() {
+        // swizzled mips are only required to fill the block linear layout
+        match layout {
+            Some(layout) => {
+                let block_linear = header.block_linear()?;
+                data_len
+                    >= block_linear.swizzled_size(
+                        layout.width_in_blocks(),
+                        layout.rows,
+                        layout.slices,
+                        layout.bytes_per_block,
+                    )
+            }
+            None => true,
+        }
+    } else {
+        // entries must at least hold the packed mip
+        if let Some(layout) = layout
+            && (mip_data.entry.scanline_length < layout.pitch
+                || (mip_data.entry.uncompressed_size as u64)
+                    < layout.pitch as u64 * layout.rows as u64)
+        {
+            return Err(Error::InvalidTexData(format!(
+                "Mipmap {level} entry is smaller than the {:?} mip size",
+                header.format
+            )));
+        }
+        data_len
+            == mip_data.entry.uncompressed_size as usize
+                * u32::max(1, header.depth as u32 >> level) as usize
+    };
+    if !size_ok {
+        return Err(Error::Internal(
+            "Mipmap data size does not match mipmap entry size".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that the format, dimensions and mip count of `header` fit the
+/// layout of its version.
+fn check_layout_limits(header: &TexHeader) -> Result<()> {
+    let version = header.version;
+    let layout = header.layout();
+    if !layout.supports_format(header.format) {
+        return Err(Error::UnsupportedByVersion(
+            version,
+            format!("format {:?}", header.format),
+        ));
+    }
+    let (width, height, depth) = (header.width, header.height, header.depth);
+    if width == 0 || height == 0 || depth == 0 {
+        return Err(Error::InvalidTexData(format!(
+            "Empty dimensions: {width}x{height}x{depth}"
+        )));
+    }
+    let mipmap_count = header.mipmap_count;
+    if mipmap_count == 0 || mipmap_count > layout.max_mipmap_count() {
+        return Err(Error::UnsupportedByVersion(
+            version,
+            format!("{mipmap_count} mipmaps"),
+        ));
+    }
+    let full_chain = width.max(height).max(depth).ilog2() + 1;
+    if mipmap_count as u32 > full_chain {
+        return Err(Error::InvalidTexData(format!(
+            "{mipmap_count} mipmaps exceed the {full_chain} levels of a {width}x{height}x{depth} texture"
+        )));
+    }
+
+    // mip entries store u32 sizes, compression infos u32 offsets
+    let mut data_size = 0u64;
+    for level in 0..mipmap_count as u32 {
+        let mip =
+            mip_layout(header, level).ok_or(Error::UnsupportedTexFormat(header.format as u32))?;
+        let scanline_length = if header.is_swizzled() {
+            mip.pitch as u64
+        } else {
+            (mip.pitch as u64).next_multiple_of(layout.pitch_alignment as u64)
+        };
+        let size = scanline_length * mip.rows as u64;
+        if size > u32::MAX as u64 {
+            return Err(Error::UnsupportedByVersion(
+                version,
+                format!(
+                    "{width}x{height} {:?} mipmap {level} of {size} bytes",
+                    header.format
+                ),
+            ));
+        }
+        data_size += size * mip.slices as u64;
+    }
+    data_size *= header.tex_count.max(1) as u64;
+    if layout.compression_info && data_size > u32::MAX as u64 {
+        return Err(Error::UnsupportedByVersion(
+            version,
+            format!("{data_size} bytes of texture data"),
+        ));
+    }
+    Ok(())
+}
+
+/// Packed layout of a mip level.
+#[derive(Debug, Clone, Copy)]
+struct MipLayout {
+    /// Bytes per row of pixels or blocks.
+    pitch: u32,
+    /// Rows of pixels or blocks per slice.
+    rows: u32,
+    /// Depth slices.
+    slices: u32,
+    /// Bytes per pixel or block.
+    bytes_per_block: u32,
+}
+
+impl MipLayout {
+    fn width_in_blocks(&self) -> u32 {
+        self.pitch / self.bytes_per_block
+    }
+}
+
+/// Get the packed layout of a mip level.
+fn mip_layout(header: &TexHeader, level: u32) -> Option<MipLayout> {
+    let format = header.format;
+    let (_, block_height) = format.block_dimensions();
+    let height = u32::max(1, header.height as u32 >> level);
+    Some(MipLayout {
+        pitch: format.row_pitch(header.width as u32, level)?,
+        rows: height.div_ceil(block_height),
+        slices: u32::max(1, header.depth as u32 >> level),
+        bytes_per_block: format.bytes_per_block()?,
+    })
+}
+
+/// Pad every row of packed mip data to `scanline_length` bytes.
+fn pad_rows(data: &[u8], pitch: u32, scanline_length: u32) -> Vec<u8> {
+    if pitch == scanline_length || pitch == 0 {
+        return data.to_vec();
+    }
+    let mut out = Vec::with_capacity(data.len() / pitch as usize * scanline_length as usize);
+    for row in data.chunks(pitch as usize) {
+        out.extend_from_slice(row);
+        out.resize(out.len() + (scanline_length - pitch) as usize, 0);
+    }
+    out
+}
+
+/// Remove the row padding of mip data, keeping `pitch` bytes of every row.
+fn strip_row_padding(data: &[u8], scanline_length: u32, pitch: u32) -> Cow<'_, [u8]> {
+    if scanline_length <= pitch {
+        return Cow::Borrowed(data);
+    }
+    Cow::Owned(
+        data.chunks(scanline_length as usize)
+            .flat_map(|row| &row[..usize::min(row.len(), pitch as usize)])
+            .copied()
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ddsfile::DxgiFormat;
+    use strum::IntoEnumIterator as _;
+
+    use super::*;
+
+    const TEST_FILE_GDF: &str = "test_files/ch04_000_0000_1002_MB.tex.241106027";
+    const TEST_FILE_NO_GDF: &str = "test_files/uncompress_ch04_000_0000_1001_ALBD.tex.241106027";
+    const TEST_FILE_LEGACY: &str = "test_files/s_menu_iam.tex.34";
+
     #[test]
+    fn test_parse_tex() {
+        let data = std::fs::read(TEST_FILE_NO_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        eprintln!("{:#?}", tex);
+    }
+
+    #[test]
+    fn test_parse_tex_gdf() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        eprintln!("{:#?}", tex);
+    }
+
+    #[test]
+    #[ignore = "needs test_files/s_menu_iam.tex.34, which is not distributed"]
     fn test_parse_tex_legacy() {
         let data = std::fs::read(TEST_FILE_LEGACY).unwrap();
+        let mut reader = std::io::Cursor::new(&data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        tex.to_dds(tex.header.mipmap_count as usize).unwrap();
+        let bytes = tex.as_bytes().unwrap();
+        assert_eq!(data, bytes);
+    }
+
+    #[test]
+    fn test_parse_tex_legacy_bytes() {
+        // RE2 file with a 4x2 RGBA texture and its 2x1 mip, absolute mip offsets
+        let mip0: Vec<u8> = (0..32).collect();
+        let mip1: Vec<u8> = (32..40).collect();
+        let mut data = Vec::new();
+        data.extend_from_slice(b"TEX\0");
+        data.extend_from_slice(&10u32.to_le_bytes());
+        data.extend_from_slice(&[4, 0, 2, 0, 1, 0]); // width, height, depth
+        data.extend_from_slice(&[2, 1]); // mipmap_count, tex_count
+        data.extend_from_slice(&0x1Cu32.to_le_bytes()); // R8G8B8A8Unorm
+        data.extend_from_slice(&(-1i32).to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+        for (offset, scanline_length, mip) in [(64u64, 16u32, &mip0), (96, 8, &mip1)] {
+            data.extend_from_slice(&offset.to_le_bytes());
+            data.extend_from_slice(&scanline_length.to_le_bytes());
+            data.extend_from_slice(&(mip.len() as u32).to_le_bytes());
+        }
+        data.extend_from_slice(&mip0);
+        data.extend_from_slice(&mip1);
+
+        let tex = Tex::from_reader(&mut std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(tex.header.tex_version(), TexVersion::RE2);
+        assert_eq!((tex.header.width, tex.header.height), (4, 2));
+        assert_eq!(tex.header.mipmap_count, 2);
+        assert_eq!(tex.header.mipmap_header_size, 0);
+        assert_eq!(tex.header.format, TexFormat::R8G8B8A8Unorm);
+        assert_eq!(tex.mip_datas[1].entry.offset, 96);
+        assert_eq!(tex.to_dds(2).unwrap().data, [mip0, mip1].concat());
+        assert_eq!(tex.as_bytes().unwrap(), data);
+    }
+
+    #[test]
+    fn test_tex_write_to() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(&data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        let mut bytes = Vec::new();
+        tex.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, data);
+
+        // stale tables are rebuilt from the mip data
+        let mut tex = tex;
+        let entries: Vec<_> = tex
+            .mip_datas
+            .iter()
+            .map(|mip| mip.entry.as_bytes())
+            .collect();
+        tex.batch_decompress().unwrap();
+        tex.header.mipmap_header_size = 0;
+        for mip_data in &mut tex.mip_datas {
+            mip_data.entry.offset = 0;
+            mip_data.compression_info.compressed_offset = 1;
+            mip_data.compression_info.compressed_size = 2;
+        }
+        let mut bytes = Vec::new();
+        tex.write_to(&mut bytes).unwrap();
+        let mut reader = std::io::Cursor::new(&bytes);
+        let new_tex = Tex::from_reader(&mut reader).unwrap();
+        assert_eq!(new_tex.header.mipmap_count, tex.header.mipmap_count);
+        for ((new_mip, mip), entry) in new_tex.mip_datas.iter().zip(&tex.mip_datas).zip(&entries) {
+            assert_eq!(new_mip.texture_data, mip.texture_data);
+            assert_eq!(&new_mip.entry.as_bytes(), entry);
+        }
+
+        // entry sizes must match the mip data
+        let mut bad = tex.clone();
+        bad.mip_datas[1].entry.uncompressed_size += 1;
+        assert!(bad.write_to(&mut Vec::new()).is_err());
+
+        // GDeflate data needs the compression info table
+        let mut bad = Tex::from_reader(&mut std::io::Cursor::new(&data)).unwrap();
+        bad.header.version = TexVersion::DD2.0;
+        assert!(matches!(
+            bad.write_to(&mut Vec::new()),
+            Err(Error::UnsupportedByVersion(..))
+        ));
+
+        // legacy versions locate mip data with the mip entry offsets
+        let dds = tex.to_dds(tex.header.mipmap_count as usize).unwrap();
+        let mut tex = Tex::from_dds(&dds, TexVersion::RE2).unwrap();
+        for mip_data in &mut tex.mip_datas {
+            mip_data.entry.offset = 0;
+        }
+        let mut bytes = Vec::new();
+        tex.write_to(&mut bytes).unwrap();
+        let mut reader = std::io::Cursor::new(&bytes);
+        let new_tex = Tex::from_reader(&mut reader).unwrap();
+        assert_eq!(
+            new_tex
+                .to_dds(tex.header.mipmap_count as usize)
+                .unwrap()
+                .data,
+            dds.data
+        );
+    }
+
+    #[test]
+    fn test_tex_versions_rw() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
         let mut reader = std::io::Cursor::new(data);
-        let err = Tex::from_reader(&mut reader).unwrap_err();
-        assert!(matches!(err, crate::error::Error::InvalidTexData(_)));
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        let dds = tex.to_dds(tex.header.mipmap_count as usize).unwrap();
+
+        for game in crate::version::Game::iter() {
+            let version = game.tex_version().0;
+            let new_tex = Tex::from_dds(&dds, game.tex_version()).unwrap();
+            let bytes = new_tex.as_bytes().unwrap();
+
+            let mut reader = std::io::Cursor::new(&bytes);
+            let new_tex = Tex::from_reader(&mut reader).unwrap();
+            assert_eq!(new_tex.header.version, version);
+            assert_eq!(new_tex.header.mipmap_count, tex.header.mipmap_count);
+            assert_eq!(new_tex.header.tex_count, tex.header.tex_count);
+            assert_eq!(
+                new_tex
+                    .to_dds(tex.header.mipmap_count as usize)
+                    .unwrap()
+                    .data,
+                dds.data,
+                "version {version}"
+            );
+            assert_eq!(new_tex.as_bytes().unwrap(), bytes, "version {version}");
+        }
+    }
+
+    #[test]
+    fn test_mip_uncompressed_size() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(&data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        for mip_data in &tex.mip_datas {
+            assert_eq!(
+                mip_data.uncompressed_size().unwrap(),
+                mip_data.uncompressed_data(None).unwrap().len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_tex_reader() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(&data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+
+        let file = std::fs::File::open(TEST_FILE_GDF).unwrap();
+        let mut tex_reader = TexReader::new(io::BufReader::new(file)).unwrap();
+        assert_eq!(tex_reader.mip_entries().len(), tex.mip_datas.len());
+        // read out of order
+        for level in (0..tex.header.mipmap_count as usize).rev() {
+            let mip_data = tex_reader.read_mip(0, level).unwrap();
+            assert_eq!(mip_data.texture_data, tex.mip_datas[level].texture_data);
+        }
+        assert!(tex_reader.read_mip(1, 0).is_err());
+
+        let bytes = tex_reader.read_tex().unwrap().as_bytes().unwrap();
+        assert_eq!(data, bytes);
+
+        // a compressed size past the end of the stream
+        let mut bad = data.clone();
+        let num_mipmaps = tex.mip_datas.len();
+        let info_start = tex.header.size() + num_mipmaps * MipEntry::SIZE;
+        bad[info_start..][..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut tex_reader = TexReader::new(std::io::Cursor::new(bad)).unwrap();
+        let err = tex_reader.read_mip(0, 0).unwrap_err();
+        assert!(matches!(err, Error::InvalidTexData(..)));
     }
 
     #[test]
@@ -468,6 +1833,58 @@ mod tests {
         assert_eq!(bytes, &data[0..bytes.len()]);
     }
 
+    #[test]
+    fn test_mip_tables_rw() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(&data);
+        let header = TexHeader::from_reader(&mut reader).unwrap();
+        let header_size = header.size();
+
+        let entry = MipEntry::from_reader(&mut reader).unwrap();
+        assert_eq!(
+            entry.offset(),
+            u64::from_le_bytes(data[header_size..header_size + 8].try_into().unwrap())
+        );
+        assert_eq!(entry.scanline_length(), header.width as u32 / 4 * 16);
+        assert_eq!(
+            entry.uncompressed_size(),
+            entry.scanline_length() * header.height as u32 / 4
+        );
+        assert_eq!(
+            entry.as_bytes(),
+            &data[header_size..header_size + MipEntry::SIZE]
+        );
+
+        let table_size = header.mipmap_count as usize * MipEntry::SIZE;
+        let start = header_size + table_size;
+        let mut reader = std::io::Cursor::new(&data[start..]);
+        let info = CompressionInfo::from_reader(&mut reader).unwrap();
+        assert_eq!(info.compressed_offset(), 0);
+        assert_eq!(
+            info.compressed_size(),
+            u32::from_le_bytes(data[start..start + 4].try_into().unwrap())
+        );
+        assert_eq!(info.as_bytes(), &data[start..start + CompressionInfo::SIZE]);
+    }
+
+    #[test]
+    fn test_check_mip_size() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut tex = Tex::from_reader(&mut std::io::Cursor::new(data)).unwrap();
+        tex.batch_decompress().unwrap();
+        for (level, mip_data) in tex.mip_datas.iter().enumerate() {
+            check_mip_size(&tex.header, level as u32, mip_data).unwrap();
+        }
+
+        // entry too small for the format, even if it matches the data
+        let mip_data = &mut tex.mip_datas[0];
+        mip_data.entry.uncompressed_size /= 2;
+        mip_data
+            .texture_data
+            .truncate(mip_data.entry.uncompressed_size as usize);
+        assert!(check_mip_size(&tex.header, 0, &tex.mip_datas[0]).is_err());
+    }
+
     #[test]
     fn test_tex_rw() {
         let mut data = std::fs::read(TEST_FILE_GDF).unwrap();
@@ -492,6 +1909,151 @@ mod tests {
         eprintln!("{:#?}", tex);
     }
 
+    #[test]
+    fn test_tex_batch_compress() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(&data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+
+        let mut decompressed = tex.clone();
+        decompressed.batch_decompress().unwrap();
+        let mut compressed = decompressed.clone();
+        compressed
+            .batch_compress(CompressionLevel::Level12)
+            .unwrap();
+        assert!(compressed.mip_datas.iter().all(|mip| mip.is_compressed()));
+
+        let bytes = compressed.as_bytes().unwrap();
+        assert!(bytes.len() < decompressed.clone().as_bytes().unwrap().len());
+
+        // read back the written file
+        let mut reader = std::io::Cursor::new(bytes);
+        let compressed = Tex::from_reader(&mut reader).unwrap();
+        for (new_mip, mip) in compressed.mip_datas.iter().zip(&tex.mip_datas) {
+            assert_eq!(new_mip.entry.offset, mip.entry.offset);
+            assert_eq!(
+                new_mip.uncompressed_data(None).unwrap(),
+                mip.uncompressed_data(None).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_tex_batch_compress_options() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(&data);
+        let mut tex = Tex::from_reader(&mut reader).unwrap();
+        tex.batch_decompress().unwrap();
+        // incompressible first mip
+        let mut state = 0x12345678u32;
+        for byte in &mut tex.mip_datas[0].texture_data {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = state as u8;
+        }
+        let decompressed = tex.clone();
+
+        let options = CompressOptions {
+            only_if_smaller: true,
+            ..CompressOptions::new(CompressionLevel::Level6)
+        };
+        tex.batch_compress_with(&options).unwrap();
+        assert!(!tex.mip_datas[0].is_compressed());
+        assert!(tex.mip_datas[1].is_compressed());
+        // tile size index in the stream header
+        assert_eq!(
+            tex.mip_datas[1].texture_data[4] & 0x3,
+            TileSize::KiB64.index() as u8
+        );
+
+        let bytes = tex.as_bytes().unwrap();
+        let mut reader = std::io::Cursor::new(bytes);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        for (mip, expected) in tex.mip_datas.iter().zip(&decompressed.mip_datas) {
+            assert_eq!(
+                mip.uncompressed_data(None).unwrap(),
+                expected.uncompressed_data(None).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_tex_convert_to_version() {
+        let data = std::fs::read("test_files/ch04_000_0000_1001_ALBD.tex.241106027").unwrap();
+        let mut reader = std::io::Cursor::new(&data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        let mipmap_count = tex.header.mipmap_count as usize;
+        let dds = tex.to_dds(mipmap_count).unwrap();
+
+        let options = CompressOptions::new(CompressionLevel::Level12);
+        let rise = tex
+            .convert_to_version(TexVersion::MH_RISE, &options)
+            .unwrap();
+        assert!(rise.mip_datas.iter().all(|mip| !mip.is_compressed()));
+        let bytes = rise.as_bytes().unwrap();
+        let mut reader = std::io::Cursor::new(bytes);
+        let rise = Tex::from_reader(&mut reader).unwrap();
+        assert_eq!(rise.header.version, TexVersion::MH_RISE.0);
+        assert_eq!(rise.to_dds(mipmap_count).unwrap().data, dds.data);
+
+        // converting back matches the shipped file
+        let wilds = rise
+            .convert_to_version(TexVersion::MH_WILDS, &options)
+            .unwrap();
+        assert!(wilds.mip_datas.iter().all(|mip| mip.is_compressed()));
+        assert_eq!(
+            wilds.header.as_bytes().unwrap(),
+            tex.header.as_bytes().unwrap()
+        );
+        for (new_mip, mip) in wilds.mip_datas.iter().zip(&tex.mip_datas) {
+            assert_eq!(new_mip.entry.as_bytes(), mip.entry.as_bytes());
+            if cfg!(feature = "rust-gdeflate") {
+                // the pure-Rust encoder picks different matches than libdeflate
+                assert_eq!(
+                    new_mip.uncompressed_data(None).unwrap(),
+                    mip.uncompressed_data(None).unwrap()
+                );
+                continue;
+            }
+            assert_eq!(
+                new_mip.compression_info.as_bytes(),
+                mip.compression_info.as_bytes()
+            );
+            assert_eq!(new_mip.texture_data, mip.texture_data);
+        }
+
+        let mut astc = tex.clone();
+        astc.header.format = TexFormat::Astc4x4Unorm;
+        let err = astc
+            .convert_to_version(TexVersion::RE2, &options)
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsupportedByVersion(..)));
+
+        // 256-byte aligned rows of the largest mip overflow the u32 entry size
+        let mut huge = tex.clone();
+        huge.header.format = TexFormat::R32G32B32A32Float;
+        (huge.header.width, huge.header.height) = (u16::MAX, 4096);
+        (huge.header.mipmap_count, huge.header.tex_count) = (1, 1);
+        let packed = TexHeader {
+            version: TexVersion::RE2.0,
+            ..huge.header.clone()
+        };
+        check_layout_limits(&packed).unwrap();
+        let err = huge
+            .convert_to_version(TexVersion::MH_WILDS, &options)
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsupportedByVersion(..)));
+
+        // more mipmaps than the dimensions allow
+        let mut tiny = tex.clone();
+        (tiny.header.width, tiny.header.height) = (4, 4);
+        let err = tiny
+            .convert_to_version(TexVersion::MH_RISE, &options)
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidTexData(..)));
+    }
+
     #[test]
     fn test_tex_to_dds() {
         let mut data = std::fs::read("test_files/ch04_000_0000_1001_ALBD.tex.241106027").unwrap();
@@ -500,6 +2062,470 @@ mod tests {
         tex.to_dds(tex.header.mipmap_count as usize).unwrap();
     }
 
+    #[test]
+    fn test_tex_builder() {
+        let (width, height) = (20u16, 12u16);
+        let mips = |seed: u8| -> Vec<Vec<u8>> {
+            (0..3)
+                .map(|level| {
+                    let size = (width as usize >> level) * (height as usize >> level) * 4;
+                    (0..size).map(|i| (i as u8).wrapping_mul(seed)).collect()
+                })
+                .collect()
+        };
+
+        for version in [TexVersion::RE2, TexVersion::MH_RISE, TexVersion::MH_WILDS] {
+            let tex = TexBuilder::new(version, TexFormat::R8G8B8A8Unorm, width, height)
+                .layer(mips(3))
+                .layer(mips(7))
+                .build()
+                .unwrap();
+            assert_eq!(tex.header.tex_count, 2);
+            assert_eq!(tex.header.mipmap_count, 3);
+            assert_eq!(
+                tex.mip_datas[0].entry.scanline_length,
+                80u32.next_multiple_of(version.layout().pitch_alignment)
+            );
+
+            let mut bytes = Vec::new();
+            tex.write_to(&mut bytes).unwrap();
+            let mut reader = std::io::Cursor::new(&bytes);
+            let new_tex = Tex::from_reader(&mut reader).unwrap();
+            assert_eq!(
+                new_tex.header.as_bytes().unwrap(),
+                tex.header.as_bytes().unwrap()
+            );
+            let expected: Vec<u8> = [mips(3), mips(7)].into_iter().flatten().flatten().collect();
+            assert_eq!(new_tex.to_dds(3).unwrap().data, expected);
+        }
+
+        let builder = TexBuilder::new(TexVersion::MH_WILDS, TexFormat::R8G8B8A8Unorm, 20, 12);
+        // no layers
+        assert!(builder.build().is_err());
+        // wrong mip size
+        assert!(builder.clone().layer(vec![vec![0; 10]]).build().is_err());
+        // mip counts differ between layers
+        assert!(
+            builder
+                .clone()
+                .layer(mips(1))
+                .layer(mips(1)[..2].to_vec())
+                .build()
+                .is_err()
+        );
+        // not a whole cube
+        assert!(builder.cubemap(true).layer(mips(1)).build().is_err());
+
+        // rebuilding shipped files gives the same header
+        for path in [
+            "test_files/ch04_000_0000_1001_ALBD.tex.241106027",
+            "test_files/ch04_000_0000_1002_MB.tex.241106027",
+        ] {
+            let data = std::fs::read(path).unwrap();
+            let tex = Tex::from_reader(&mut std::io::Cursor::new(&data)).unwrap();
+            let mips = (0..tex.mip_datas.len())
+                .map(|idx| tex.linear_mip_data(idx).unwrap())
+                .collect();
+            let (unkn04, unkn05) = tex.header.unknown_bytes();
+            let header = &tex.header;
+            let new_tex = TexBuilder::new(
+                header.tex_version(),
+                header.format,
+                header.width,
+                header.height,
+            )
+            .unknown_bytes(unkn04, unkn05)
+            .layer(mips)
+            .build()
+            .unwrap();
+            assert_eq!(new_tex.header.as_bytes().unwrap(), data[..header.size()]);
+        }
+    }
+
+    #[test]
+    fn test_tex_from_dds() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        let dds = tex.to_dds(tex.header.mipmap_count as usize).unwrap();
+
+        let new_tex = Tex::from_dds(&dds, tex.header.tex_version()).unwrap();
+        assert_eq!(new_tex.header.width, tex.header.width);
+        assert_eq!(new_tex.header.height, tex.header.height);
+        assert_eq!(new_tex.header.mipmap_count, tex.header.mipmap_count);
+        assert_eq!(
+            new_tex.header.mipmap_header_size,
+            tex.header.mipmap_header_size
+        );
+        assert_eq!(new_tex.header.format, tex.header.format);
+
+        // the mip count is checked against the version layout
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            height: 1,
+            width: 0x8000,
+            depth: None,
+            format: DxgiFormat::R8_UNorm,
+            mipmap_levels: Some(16),
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .unwrap();
+        dds.data = vec![0; 0xFFFF];
+        let err = Tex::from_dds(&dds, TexVersion::MH_WILDS).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedByVersion(..)));
+        let legacy = Tex::from_dds(&dds, TexVersion::RE2).unwrap();
+        assert_eq!(legacy.header.mipmap_count, 16);
+        assert_eq!(legacy.header.mipmap_header_size, 0);
+        dds.header.mip_map_count = Some(17);
+        assert!(Tex::from_dds(&dds, TexVersion::RE2).is_err());
+
+        // read back the written file
+        let bytes = new_tex.as_bytes().unwrap();
+        let mut reader = std::io::Cursor::new(bytes);
+        let new_tex = Tex::from_reader(&mut reader).unwrap();
+        for (new_mip, mip) in new_tex.mip_datas.iter().zip(&tex.mip_datas) {
+            assert_eq!(new_mip.entry.offset, mip.entry.offset);
+            assert_eq!(new_mip.entry.scanline_length, mip.entry.scanline_length);
+            assert_eq!(new_mip.entry.uncompressed_size, mip.entry.uncompressed_size);
+            assert_eq!(
+                new_mip.uncompressed_data(None).unwrap(),
+                mip.uncompressed_data(None).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_tex_array_to_dds() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        let mipmap_count = tex.header.mipmap_count as usize;
+        let layer_data = tex.to_dds(mipmap_count).unwrap().data;
+
+        // build a 3 layer array with different data per layer
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            height: tex.header.height as u32,
+            width: tex.header.width as u32,
+            depth: None,
+            format: DxgiFormat::BC7_UNorm,
+            mipmap_levels: Some(mipmap_count as u32),
+            array_layers: Some(3),
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .unwrap();
+        let layers: Vec<Vec<u8>> = (0..3u8)
+            .map(|i| layer_data.iter().map(|b| b.wrapping_add(i)).collect())
+            .collect();
+        dds.data = layers.concat();
+
+        let array = Tex::from_dds(&dds, tex.header.tex_version()).unwrap();
+        assert_eq!(array.header.tex_count, 3);
+        let array_dds = array.to_dds(mipmap_count).unwrap();
+        assert_eq!(array_dds.get_num_array_layers(), 3);
+        assert_eq!(array_dds.data, dds.data);
+
+        for (layer, layer_data) in layers.iter().enumerate() {
+            let layer_dds = array.to_dds_layer(layer, mipmap_count).unwrap();
+            assert_eq!(layer_dds.get_num_array_layers(), 1);
+            assert_eq!(&layer_dds.data, layer_data);
+        }
+
+        // first mips of every layer
+        let array_dds = array.to_dds(1).unwrap();
+        let first_mip_size = array_dds.get_main_texture_size().unwrap() as usize;
+        for (layer, layer_data) in layers.iter().enumerate() {
+            assert_eq!(
+                &array_dds.get_data(layer as u32).unwrap(),
+                &&layer_data[..first_mip_size]
+            );
+        }
+        assert!(array.to_dds_layer(3, mipmap_count).is_err());
+    }
+
+    #[test]
+    fn test_tex_to_dds_no_dxgi() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut tex = Tex::from_reader(&mut std::io::Cursor::new(data)).unwrap();
+        // same block size as BC7, but no DXGI format
+        tex.header.format = TexFormat::Astc4x4Unorm;
+        assert!(matches!(
+            tex.to_dds(1),
+            Err(Error::NoDxgiFormat(TexFormat::Astc4x4Unorm))
+        ));
+    }
+
+    /// ASTC void extent block, filled with a constant color.
+    fn astc_constant_block(color: [u8; 4]) -> [u8; 16] {
+        let mut block = [
+            0xFC, 0xFD, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        for (i, channel) in color.into_iter().enumerate() {
+            block[8 + i * 2..][..2].copy_from_slice(&(channel as u16 * 257).to_le_bytes());
+        }
+        block
+    }
+
+    /// Colors of the block rows of [`astc_tex`].
+    const ASTC_COLORS: [[u8; 4]; 3] = [[255, 0, 0, 255], [0, 255, 0, 128], [0, 0, 255, 0]];
+
+    /// 20x14 ASTC tex with 6x6 blocks, one color per block row.
+    fn astc_tex() -> Tex {
+        let data: Vec<u8> = ASTC_COLORS
+            .iter()
+            .flat_map(|&color| astc_constant_block(color).repeat(4))
+            .collect();
+        let header = TexHeader {
+            magic: TexHeader::MAGIC,
+            version: TexVersion::RE8.0,
+            width: 20,
+            height: 14,
+            depth: 1,
+            mipmap_count: 1,
+            tex_count: 1,
+            mipmap_header_size: MipEntry::SIZE as u8,
+            format: TexFormat::Astc6x6Unorm,
+            ..Default::default()
+        };
+        let entry = MipEntry {
+            offset: (header.size() + MipEntry::SIZE) as u64,
+            scanline_length: 64,
+            uncompressed_size: data.len() as u32,
+        };
+        let compression_info = CompressionInfo {
+            compressed_size: data.len() as u32,
+            compressed_offset: 0,
+        };
+        let tex = Tex {
+            header,
+            mip_datas: vec![MipData::new(entry, compression_info, data)],
+        };
+        let bytes = tex.as_bytes().unwrap();
+        Tex::from_reader(&mut std::io::Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn test_tex_astc() {
+        let tex = astc_tex();
+        assert!(tex.to_dds(1).is_err());
+
+        let astc = tex.to_astc(0, 0).unwrap();
+        assert_eq!(astc[..4], ASTC_MAGIC.to_le_bytes());
+        assert_eq!(astc[4..16], [6, 6, 1, 20, 0, 0, 14, 0, 0, 1, 0, 0]);
+        assert_eq!(astc[16..], tex.mip_datas[0].texture_data);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_tex_astc_to_rgba_image() {
+        let image = astc_tex().to_rgba_image(0).unwrap();
+        assert_eq!(image.dimensions(), (20, 14));
+        assert_eq!(image.get_pixel(0, 0).0, ASTC_COLORS[0]);
+        assert_eq!(image.get_pixel(19, 6).0, ASTC_COLORS[1]);
+        assert_eq!(image.get_pixel(19, 13).0, ASTC_COLORS[2]);
+    }
+
+    /// 16x16 RGBA cube with one solid color per face.
+    fn cubemap_dds() -> Dds {
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            height: 16,
+            width: 16,
+            depth: None,
+            format: DxgiFormat::R8G8B8A8_UNorm,
+            mipmap_levels: Some(1),
+            array_layers: Some(6),
+            caps2: Some(Caps2::CUBEMAP | Caps2::CUBEMAP_ALLFACES),
+            is_cubemap: true,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .unwrap();
+        dds.data = (0..6u8)
+            .flat_map(|face| [face * 40, 0, 255 - face * 40, 255].repeat(16 * 16))
+            .collect();
+        dds
+    }
+
+    #[test]
+    fn test_tex_cubemap() {
+        let dds = cubemap_dds();
+        let tex = Tex::from_dds(&dds, TexVersion::MH_WILDS).unwrap();
+        assert!(tex.is_cubemap());
+        assert_eq!(tex.header.tex_count, 6);
+
+        let cube_dds = tex.to_dds(1).unwrap();
+        let header10 = cube_dds.header10.as_ref().unwrap();
+        assert!(header10.misc_flag.contains(MiscFlag::TEXTURECUBE));
+        assert_eq!(header10.array_size, 1);
+        assert!(cube_dds.header.caps2.contains(Caps2::CUBEMAP_ALLFACES));
+        assert_eq!(cube_dds.data, dds.data);
+
+        // rebuilding from the exported cubemap keeps every face
+        let tex_rebuilt = Tex::from_dds(&cube_dds, TexVersion::MH_WILDS).unwrap();
+        assert_eq!(tex_rebuilt.header.tex_count, 6);
+        assert_eq!(tex_rebuilt.to_dds(1).unwrap().data, dds.data);
+
+        // cubemaps must have whole cubes
+        let mut broken = tex.clone();
+        broken.header.tex_count = 5;
+        broken.mip_datas.truncate(5);
+        let data = broken.as_bytes().unwrap();
+        assert!(Tex::from_reader(&mut std::io::Cursor::new(data)).is_err());
+    }
+
+    #[test]
+    fn test_tex_cubemap_array_rw() {
+        // 4 cubes of 13 mips, more mipmaps than a u8 counts
+        let (width, mipmap_count) = (4096u16, 13);
+        let builder = TexBuilder::new(TexVersion::MH_WILDS, TexFormat::R8Unorm, width, 1);
+        let layers: Vec<Vec<Vec<u8>>> = (0..24u8)
+            .map(|layer| {
+                (0..mipmap_count)
+                    .map(|level| vec![layer; width as usize >> level])
+                    .collect()
+            })
+            .collect();
+        let tex = layers
+            .iter()
+            .fold(builder.cubemap(true), |builder, mips| {
+                builder.layer(mips.clone())
+            })
+            .build()
+            .unwrap();
+
+        let mut bytes = Vec::new();
+        tex.write_to(&mut bytes).unwrap();
+        let new_tex = Tex::from_reader(&mut std::io::Cursor::new(&bytes)).unwrap();
+        assert!(new_tex.is_cubemap());
+        assert_eq!(new_tex.header.tex_count, 24);
+        assert_eq!(new_tex.mip_datas.len(), 24 * mipmap_count);
+        let expected: Vec<u8> = layers.into_iter().flatten().flatten().collect();
+        assert_eq!(new_tex.to_dds(mipmap_count).unwrap().data, expected);
+        assert_eq!(new_tex.as_bytes().unwrap(), bytes);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_tex_cubemap_faces() {
+        let tex = Tex::from_dds(&cubemap_dds(), TexVersion::MH_WILDS).unwrap();
+        let faces = tex.to_rgba_cubemap_faces(0, 0).unwrap();
+        for (face, image) in faces.iter().enumerate() {
+            let face = face as u8;
+            assert_eq!(image.dimensions(), (16, 16));
+            assert_eq!(
+                image.get_pixel(0, 0).0,
+                [face * 40, 0, 255 - face * 40, 255]
+            );
+        }
+        assert!(tex.to_rgba_cubemap_faces(1, 0).is_err());
+    }
+
+    /// 8x8x4 RGBA volume with one solid color per depth slice.
+    fn volume_dds() -> Dds {
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            height: 8,
+            width: 8,
+            depth: Some(4),
+            format: DxgiFormat::R8G8B8A8_UNorm,
+            mipmap_levels: Some(2),
+            array_layers: None,
+            caps2: Some(Caps2::VOLUME),
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture3D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .unwrap();
+        let mip0 = (0..4u8).flat_map(|slice| [slice * 60, 0, 0, 255].repeat(8 * 8));
+        let mip1 = (0..2u8).flat_map(|slice| [0, slice * 60, 0, 255].repeat(4 * 4));
+        dds.data = mip0.chain(mip1).collect();
+        dds
+    }
+
+    #[test]
+    fn test_tex_volume() {
+        let dds = volume_dds();
+        let tex = Tex::from_dds(&dds, TexVersion::MH_WILDS).unwrap();
+        assert!(tex.is_volume());
+        assert_eq!(tex.header.depth, 4);
+        assert_eq!(tex.header.tex_count, 1);
+
+        let volume_dds = tex.to_dds(2).unwrap();
+        let header10 = volume_dds.header10.as_ref().unwrap();
+        assert_eq!(
+            header10.resource_dimension,
+            D3D10ResourceDimension::Texture3D
+        );
+        assert!(volume_dds.header.caps2.contains(Caps2::VOLUME));
+        assert_eq!(volume_dds.get_depth(), 4);
+        assert_eq!(volume_dds.data, dds.data);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_tex_volume_slices() {
+        let tex = Tex::from_dds(&volume_dds(), TexVersion::MH_WILDS).unwrap();
+        for slice in 0..4u8 {
+            let image = tex.to_rgba_image_slice(slice as usize, 0).unwrap();
+            assert_eq!(image.dimensions(), (8, 8));
+            assert_eq!(image.get_pixel(7, 7).0, [slice * 60, 0, 0, 255]);
+        }
+        for slice in 0..2u8 {
+            let image = tex.to_rgba_image_slice(slice as usize, 1).unwrap();
+            assert_eq!(image.dimensions(), (4, 4));
+            assert_eq!(image.get_pixel(0, 0).0, [0, slice * 60, 0, 255]);
+        }
+        assert!(tex.to_rgba_image_slice(2, 1).is_err());
+    }
+
+    #[test]
+    fn test_tex_swizzle() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        let mipmap_count = tex.header.mipmap_count as usize;
+        let dds = tex.to_dds(mipmap_count).unwrap();
+
+        let mut swizzled = tex.clone();
+        swizzled.swizzle(4).unwrap();
+        assert!(swizzled.header.is_swizzled());
+        assert_eq!(swizzled.header.swizzle_height_depth, 4);
+
+        // read back the written file
+        let bytes = swizzled.as_bytes().unwrap();
+        let mut reader = std::io::Cursor::new(bytes);
+        let mut swizzled = Tex::from_reader(&mut reader).unwrap();
+        assert!(swizzled.header.is_swizzled());
+        assert_eq!(swizzled.to_dds(mipmap_count).unwrap().data, dds.data);
+
+        swizzled.deswizzle().unwrap();
+        assert!(!swizzled.header.is_swizzled());
+        for (new_mip, mip) in swizzled.mip_datas.iter().zip(&tex.mip_datas) {
+            assert_eq!(new_mip.entry.offset, mip.entry.offset);
+            assert_eq!(new_mip.entry.scanline_length, mip.entry.scanline_length);
+            assert_eq!(
+                new_mip.uncompressed_data(None).unwrap(),
+                mip.uncompressed_data(None).unwrap()
+            );
+        }
+
+        // volumes also store the block depth
+        let dds = volume_dds();
+        let mut volume = Tex::from_dds(&dds, TexVersion::MH_WILDS).unwrap();
+        volume.swizzle(4).unwrap();
+        assert_eq!(volume.header.swizzle_height_depth, 0x24);
+        let bytes = volume.as_bytes().unwrap();
+        let mut volume = Tex::from_reader(&mut std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(volume.to_dds(2).unwrap().data, dds.data);
+
+        volume.header.swizzle_width = 1;
+        assert!(volume.to_dds(2).is_err());
+    }
+
     #[cfg(feature = "image")]
     #[test]
     fn test_tex_to_rgba_image() {
@@ -511,4 +2537,37 @@ mod tests {
             .save("test_files/ch04_000_0000_1001_ALBD.png")
             .unwrap();
     }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_tex_from_rgba_image() {
+        let data = std::fs::read(TEST_FILE_GDF).unwrap();
+        let mut reader = std::io::Cursor::new(data);
+        let tex = Tex::from_reader(&mut reader).unwrap();
+        let image = tex.to_rgba_image(0).unwrap();
+
+        let version = tex.header.tex_version();
+        for format in [
+            TexFormat::Bc1UnormSrgb,
+            TexFormat::Bc3Unorm,
+            TexFormat::Bc4Unorm,
+            TexFormat::Bc5Unorm,
+            TexFormat::Bc7Unorm,
+        ] {
+            let options = ImageEncodeOptions {
+                quality: image_dds::Quality::Fast,
+                mipmaps: image_dds::Mipmaps::GeneratedExact(tex.header.mipmap_count as u32),
+                ..ImageEncodeOptions::new(version)
+            };
+            let new_tex = Tex::from_rgba_image(&image, format, &options).unwrap();
+            assert_eq!(new_tex.header.format, format);
+            assert_eq!(new_tex.header.mipmap_count, tex.header.mipmap_count);
+
+            let bytes = new_tex.as_bytes().unwrap();
+            let mut reader = std::io::Cursor::new(bytes);
+            let new_tex = Tex::from_reader(&mut reader).unwrap();
+            let new_image = new_tex.to_rgba_image(0).unwrap();
+            assert_eq!(new_image.dimensions(), image.dimensions());
+        }
+    }
 }
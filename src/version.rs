@@ -0,0 +1,282 @@
+//! Tex file versions and the RE Engine titles using them.
+
+use std::path::Path;
+
+use strum::IntoEnumIterator as _;
+
+use crate::format::TexFormat;
+
+/// RE Engine title.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    strum::EnumIter,
+    strum::EnumString,
+    strum::Display,
+    strum::IntoStaticStr,
+)]
+#[strum(ascii_case_insensitive)]
+pub enum Game {
+    RE7,
+    RE2,
+    DMC5,
+    RE3,
+    MHRise,
+    RE8,
+    RE2RT,
+    RE3RT,
+    RE7RT,
+    RE4,
+    SF6,
+    DD2,
+    MHWilds,
+}
+
+impl Game {
+    /// Full title of the game.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Game::RE7 => "Resident Evil 7",
+            Game::RE2 => "Resident Evil 2",
+            Game::DMC5 => "Devil May Cry 5",
+            Game::RE3 => "Resident Evil 3",
+            Game::MHRise => "Monster Hunter Rise",
+            Game::RE8 => "Resident Evil Village",
+            Game::RE2RT => "Resident Evil 2 (Ray Tracing)",
+            Game::RE3RT => "Resident Evil 3 (Ray Tracing)",
+            Game::RE7RT => "Resident Evil 7 (Ray Tracing)",
+            Game::RE4 => "Resident Evil 4",
+            Game::SF6 => "Street Fighter 6",
+            Game::DD2 => "Dragon's Dogma 2",
+            Game::MHWilds => "Monster Hunter Wilds",
+        }
+    }
+
+    /// Tex file version used by the game.
+    pub fn tex_version(&self) -> TexVersion {
+        match self {
+            Game::RE7 => TexVersion::RE7,
+            Game::RE2 => TexVersion::RE2,
+            Game::DMC5 => TexVersion::DMC5,
+            Game::RE3 => TexVersion::RE3,
+            Game::MHRise => TexVersion::MH_RISE,
+            Game::RE8 => TexVersion::RE8,
+            Game::RE2RT | Game::RE3RT => TexVersion::RE_RT,
+            Game::RE7RT => TexVersion::RE7_RT,
+            Game::RE4 => TexVersion::RE4,
+            Game::SF6 => TexVersion::SF6,
+            Game::DD2 => TexVersion::DD2,
+            Game::MHWilds => TexVersion::MH_WILDS,
+        }
+    }
+
+    /// Tex file layout rules of the game.
+    pub fn layout(&self) -> TexLayout {
+        match self {
+            Game::RE7 | Game::RE2 | Game::DMC5 | Game::RE3 => TexLayout::LEGACY,
+            Game::MHRise | Game::RE8 | Game::RE2RT | Game::RE3RT | Game::RE7RT => {
+                TexLayout::SWIZZLE_BLOCK
+            }
+            Game::RE4 | Game::SF6 | Game::DD2 => TexLayout::PITCH_ALIGNED,
+            Game::MHWilds => TexLayout::COMPRESSION_INFO,
+        }
+    }
+}
+
+/// Layout rules of a Tex file version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TexLayout {
+    /// Header stores `tex_count` and `mipmap_header_size`,
+    /// instead of `mipmap_count` and `tex_count`.
+    pub mipmap_header_size: bool,
+    /// Header has the swizzle data block.
+    pub swizzle_block: bool,
+    /// Mip table is followed by a compression info table.
+    pub compression_info: bool,
+    /// Row pitch alignment of mip data in bytes.
+    pub pitch_alignment: u32,
+}
+
+impl TexLayout {
+    /// RE7, RE2, DMC5 and RE3.
+    const LEGACY: TexLayout = TexLayout {
+        mipmap_header_size: false,
+        swizzle_block: false,
+        compression_info: false,
+        pitch_alignment: 1,
+    };
+    /// MH Rise, RE8 and ray tracing updates.
+    const SWIZZLE_BLOCK: TexLayout = TexLayout {
+        mipmap_header_size: true,
+        swizzle_block: true,
+        ..Self::LEGACY
+    };
+    /// RE4, SF6 and DD2.
+    const PITCH_ALIGNED: TexLayout = TexLayout {
+        pitch_alignment: 256,
+        ..Self::SWIZZLE_BLOCK
+    };
+    /// MH Wilds.
+    const COMPRESSION_INFO: TexLayout = TexLayout {
+        compression_info: true,
+        ..Self::PITCH_ALIGNED
+    };
+
+    /// Size of the header in bytes.
+    pub fn header_size(&self) -> usize {
+        if self.swizzle_block { 40 } else { 32 }
+    }
+
+    /// Maximum mipmap count the header can store.
+    pub fn max_mipmap_count(&self) -> u8 {
+        if self.mipmap_header_size {
+            // mipmap_header_size is a u8 of 16-byte entries
+            (u8::MAX as usize / 16) as u8
+        } else {
+            u8::MAX
+        }
+    }
+
+    /// Whether the layout can store textures of `format`.
+    ///
+    /// ASTC textures only come from Switch builds, which use the swizzle block.
+    pub fn supports_format(&self, format: TexFormat) -> bool {
+        match format {
+            TexFormat::ViaExtension | TexFormat::ForceUint => false,
+            format if format.is_astc() => self.swizzle_block,
+            _ => true,
+        }
+    }
+}
+
+/// Tex file version, the number after `.tex.` in file names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TexVersion(pub u32);
+
+impl TexVersion {
+    pub const RE7: TexVersion = TexVersion(8);
+    pub const RE2: TexVersion = TexVersion(10);
+    pub const DMC5: TexVersion = TexVersion(11);
+    pub const RE3: TexVersion = TexVersion(190820018);
+    pub const MH_RISE: TexVersion = TexVersion(28);
+    pub const RE8: TexVersion = TexVersion(30);
+    /// RE2 and RE3 ray tracing updates.
+    pub const RE_RT: TexVersion = TexVersion(34);
+    pub const RE7_RT: TexVersion = TexVersion(35);
+    pub const RE4: TexVersion = TexVersion(143221013);
+    pub const SF6: TexVersion = TexVersion(143230113);
+    pub const DD2: TexVersion = TexVersion(760230703);
+    pub const MH_WILDS: TexVersion = TexVersion(241106027);
+
+    /// Detect the version from a `.tex.<version>` file name.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<TexVersion> {
+        let file_name = path.as_ref().file_name()?.to_str()?;
+        let (stem, version) = file_name.rsplit_once('.')?;
+        let (_, extension) = stem.rsplit_once('.')?;
+        if !extension.eq_ignore_ascii_case("tex") {
+            return None;
+        }
+        version.parse().ok().map(TexVersion)
+    }
+
+    /// Games using this version.
+    pub fn games(&self) -> Vec<Game> {
+        Game::iter()
+            .filter(|game| game.tex_version() == *self)
+            .collect()
+    }
+
+    /// Whether any known game uses this version.
+    pub fn is_known(&self) -> bool {
+        Game::iter().any(|game| game.tex_version() == *self)
+    }
+
+    /// Layout rules of this version.
+    ///
+    /// Unknown versions are guessed from the version number.
+    pub fn layout(&self) -> TexLayout {
+        if let Some(game) = Game::iter().find(|game| game.tex_version() == *self) {
+            return game.layout();
+        }
+        match self.0 {
+            0..=11 => TexLayout::LEGACY,
+            12..=27 => TexLayout {
+                mipmap_header_size: true,
+                ..TexLayout::LEGACY
+            },
+            28..=99_999_999 => TexLayout::SWIZZLE_BLOCK,
+            _ => TexLayout::PITCH_ALIGNED,
+        }
+    }
+}
+
+impl From<Game> for TexVersion {
+    fn from(game: Game) -> Self {
+        game.tex_version()
+    }
+}
+
+impl std::fmt::Display for TexVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_from_path() {
+        assert_eq!(
+            TexVersion::from_path("test_files/ch04_000_0000_1001_ALBD.tex.241106027"),
+            Some(TexVersion::MH_WILDS)
+        );
+        assert_eq!(
+            TexVersion::from_path("s_menu_iam.TEX.34"),
+            Some(TexVersion::RE_RT)
+        );
+        assert_eq!(TexVersion::from_path("s_menu_iam.tex"), None);
+        assert_eq!(TexVersion::from_path("s_menu_iam.mesh.34"), None);
+    }
+
+    #[test]
+    fn test_version_games() {
+        assert_eq!(TexVersion::MH_WILDS.games(), vec![Game::MHWilds]);
+        assert_eq!(TexVersion::RE_RT.games(), vec![Game::RE2RT, Game::RE3RT]);
+        assert!(TexVersion(12345).games().is_empty());
+        assert_eq!("mhwilds".parse::<Game>().unwrap(), Game::MHWilds);
+    }
+
+    #[test]
+    fn test_version_layout() {
+        // layout rules of the original version checks
+        for game in Game::iter() {
+            let version = game.tex_version().0;
+            let layout = game.layout();
+            assert_eq!(
+                layout.mipmap_header_size,
+                version > 11 && version != 190820018
+            );
+            assert_eq!(layout.swizzle_block, version > 27 && version != 190820018);
+        }
+        assert!(!TexVersion(12).layout().swizzle_block);
+        assert!(TexVersion::MH_WILDS.layout().compression_info);
+        assert!(
+            !TexVersion::RE2
+                .layout()
+                .supports_format(TexFormat::Astc4x4Unorm)
+        );
+        assert!(
+            TexVersion::MH_RISE
+                .layout()
+                .supports_format(TexFormat::Astc4x4Unorm)
+        );
+        assert_eq!(TexVersion::MH_WILDS.layout().max_mipmap_count(), 15);
+        assert_eq!(TexVersion::RE3.layout().header_size(), 32);
+    }
+}